@@ -18,6 +18,7 @@
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use syn::{
+    ext::IdentExt,
     parse::{Parse, ParseStream},
     DeriveInput, Token,
 };
@@ -26,12 +27,16 @@ struct ParsedErrors {
     ident: syn::Ident,
     generics: syn::Generics,
     variants: Vec<Variant>,
+    no_error: bool,
+    cold: bool,
 }
 
 enum Message {
     None,
     Single(String),
     Multiline(String, String),
+    /// a path or call giving the message when formatting
+    Expr(syn::Expr),
 }
 
 struct Variant {
@@ -39,6 +44,11 @@ struct Variant {
     fields: syn::Fields,
     msg: Message,
     from: bool,
+    code: Option<syn::Expr>,
+    /// the explicit discriminant, used as the code if there is none
+    discriminant: Option<syn::Expr>,
+    /// which fields get formatted with `Debug` instead of `Display`
+    debug: Vec<bool>,
 }
 
 struct AttrArg {
@@ -49,8 +59,8 @@ struct AttrArg {
 impl Parse for AttrArg {
     fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
         let ident = input.parse()?;
-        let value = if input.parse::<Token![=]>().is_ok() {
-            input.parse::<syn::Expr>().ok()
+        let value = if input.parse::<Option<Token![=]>>()?.is_some() {
+            Some(input.parse()?)
         } else {
             None
         };
@@ -85,20 +95,41 @@ fn parse_attr_doc(a: &syn::Attribute) -> Option<&syn::Expr> {
     Some(&nameval.value)
 }
 
-fn parse_attr(a: &syn::Attribute) -> Option<AttrArgs> {
+fn parse_attr(a: &syn::Attribute) -> syn::Result<Option<AttrArgs>> {
     if !matches!(a.style, syn::AttrStyle::Outer) {
-        return None;
+        return Ok(None);
     }
     let syn::Meta::List(ref list) = a.meta else {
-        return None;
+        return Ok(None);
     };
     if !matches!(list.delimiter, syn::MacroDelimiter::Paren(_)) {
-        return None;
+        return Ok(None);
     }
     if !list.path.is_ident("err") {
-        return None;
+        return Ok(None);
+    }
+    list.parse_args().map(Some)
+}
+
+/// parse all the `#[err(...)]` attributes, complaining about any
+/// arguments not in `known`
+fn parse_attrs(attrs: &[syn::Attribute], known: &[&str]) -> syn::Result<Vec<AttrArg>> {
+    let mut args = vec![];
+    for attr in attrs {
+        for arg in parse_attr(attr)?.into_iter().flat_map(|a| a.0) {
+            if !known.iter().any(|k| arg.ident == k) {
+                return Err(syn::Error::new(
+                    arg.ident.span(),
+                    format!(
+                        "unknown err attribute, expected one of: {}",
+                        known.join(", ")
+                    ),
+                ));
+            }
+            args.push(arg);
+        }
     }
-    Some(list.parse_args().expect("could not parse attr args"))
+    Ok(args)
 }
 
 fn expr_str(a: &syn::Expr) -> Option<String> {
@@ -112,24 +143,39 @@ fn expr_str(a: &syn::Expr) -> Option<String> {
     .map(|s| s.strip_prefix(' ').unwrap_or(&s).to_string())
 }
 
-fn parse_variant(v: syn::Variant) -> Variant {
+fn parse_variant(v: syn::Variant) -> syn::Result<Variant> {
     let mut doc = v
         .attrs
         .iter()
         .filter_map(parse_attr_doc)
         .filter_map(expr_str);
-    let mut args = v.attrs.iter().filter_map(parse_attr);
-    let amsg = args
-        .clone()
-        .filter_map(|a| {
-            a.0.into_iter()
-                .find(|a| a.ident == "msg")
-                .and_then(|a| a.value)
+    let args = parse_attrs(&v.attrs, &["msg", "from", "code", "debug"])?;
+    let value = |name| {
+        args.iter()
+            .rfind(|a| a.ident == name)
+            .map(|a| {
+                a.value
+                    .clone()
+                    .ok_or_else(|| syn::Error::new(a.ident.span(), format!("{name} needs a value")))
+            })
+            .transpose()
+    };
+    let amsg = value("msg")?
+        .map(|e| {
+            if let Some(s) = expr_str(&e) {
+                Ok(Message::Single(s))
+            } else if matches!(e, syn::Expr::Path(_) | syn::Expr::Call(_)) {
+                Ok(Message::Expr(e))
+            } else {
+                Err(syn::Error::new_spanned(
+                    e,
+                    "msg should be a string, path, or function call",
+                ))
+            }
         })
-        .next_back()
-        .and_then(|e| expr_str(&e));
+        .transpose()?;
     let msg = if let Some(amsg) = amsg {
-        Message::Single(amsg)
+        amsg
     } else if let Some(msg) = doc.next() {
         let extra: Vec<_> = doc.collect();
         if extra.is_empty() {
@@ -140,30 +186,127 @@ fn parse_variant(v: syn::Variant) -> Variant {
     } else {
         Message::None
     };
-    let from = args
-        .find_map(|a| a.0.into_iter().find(|a| a.ident == "from"))
-        .is_some();
-    Variant {
+    let from = args.iter().any(|a| a.ident == "from");
+    let code = value("code")?;
+    let all_debug = args.iter().any(|a| a.ident == "debug");
+    let debug = v
+        .fields
+        .iter()
+        .map(|f| {
+            let args = parse_attrs(&f.attrs, &["debug"])?;
+            Ok(all_debug || !args.is_empty())
+        })
+        .collect::<syn::Result<_>>()?;
+    Ok(Variant {
         ident: v.ident,
         fields: v.fields,
         msg,
         from,
-    }
+        code,
+        discriminant: v.discriminant.map(|(_, d)| d),
+        debug,
+    })
 }
 
-fn parse_derive(ast: DeriveInput) -> ParsedErrors {
+fn parse_derive(ast: DeriveInput) -> syn::Result<ParsedErrors> {
+    let args = parse_attrs(&ast.attrs, &["no_error", "cold"])?;
+    let no_error = args.iter().any(|a| a.ident == "no_error");
+    let cold = args.iter().any(|a| a.ident == "cold");
     let ident = ast.ident;
     let generics = ast.generics;
     let syn::Data::Enum(body) = ast.data else {
-        panic!("only enums are supported")
+        return Err(syn::Error::new(ident.span(), "only enums are supported"));
     };
-    let variants = body.variants.into_iter().map(parse_variant).collect();
+    let variants = body
+        .variants
+        .into_iter()
+        .map(parse_variant)
+        .collect::<syn::Result<_>>()?;
 
-    ParsedErrors {
+    Ok(ParsedErrors {
         ident,
         generics,
         variants,
+        no_error,
+        cold,
+    })
+}
+
+fn generate_froms<'a>(
+    ident: &'a syn::Ident,
+    generics: &'a syn::Generics,
+    variants: &'a [Variant],
+) -> impl Iterator<Item = TokenStream> + 'a {
+    variants.iter().filter_map(move |v| {
+        if !v.from {
+            return None;
+        }
+        let name = &v.ident;
+        let fields: Vec<_> = v.fields.iter().collect();
+        let [field] = fields[..] else {
+            return Some(
+                syn::Error::new(
+                    name.span(),
+                    "automatically deriving From is only supported with a single field",
+                )
+                .to_compile_error(),
+            );
+        };
+        let ty = &field.ty;
+        let construct = if let Some(fname) = &field.ident {
+            quote!(Self::#name { #fname: inner })
+        } else {
+            quote!(Self::#name(inner))
+        };
+
+        Some(quote! {
+            #[automatically_derived]
+            impl #generics ::core::convert::From<#ty> for #ident #generics {
+                #[inline]
+                fn from(inner: #ty) -> Self {
+                    #construct
+                }
+            }
+        })
+    })
+}
+
+fn generate_code(
+    ident: &syn::Ident,
+    generics: &syn::Generics,
+    variants: &[Variant],
+) -> Option<TokenStream> {
+    // only add a code method when asked to, so we do not step on
+    // anyone's toes
+    if variants.iter().all(|v| v.code.is_none()) {
+        return None;
     }
+    let arms = variants.iter().map(|v| {
+        let name = &v.ident;
+        let pattern = match v.fields {
+            syn::Fields::Named(_) => quote!({ .. }),
+            syn::Fields::Unnamed(_) => quote!((..)),
+            syn::Fields::Unit => quote!(),
+        };
+        let code = match (&v.code, &v.discriminant) {
+            (Some(c), _) => quote!(#c),
+            (None, Some(d)) => quote!((#d) as i32),
+            (None, None) => quote!(1),
+        };
+        quote!(#ident::#name #pattern => #code)
+    });
+    Some(quote! {
+        #[automatically_derived]
+        impl #generics #ident #generics {
+            /// the code given with `#[err(code = N)]`, otherwise the
+            /// discriminant, or 1 if there is neither
+            pub fn code(&self) -> i32 {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+    })
 }
 
 fn generate(parsed: ParsedErrors) -> TokenStream {
@@ -171,6 +314,8 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
         ident,
         generics,
         variants,
+        no_error,
+        cold,
     } = parsed;
 
     let arms = variants.iter().map(|v| {
@@ -178,14 +323,17 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
             ident: name,
             fields,
             msg,
+            debug,
             ..
         } = v;
+        let spec = |fnum: usize| if debug[fnum] { "{:?}" } else { "{}" };
         let bmsg = match msg {
             Message::None => {
-                let name = name.to_string();
+                let name = name.unraw().to_string();
                 quote!(#name)
             }
             Message::Single(s) | Message::Multiline(s, _) => quote!(#s),
+            Message::Expr(e) => quote!(#e),
         };
         let mut set = quote!();
         let mut get = vec![];
@@ -211,7 +359,7 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
                     if fnum > 0 {
                         fmt.push(quote!(", "));
                     }
-                    let fo = format!("{fnm}: {{}}");
+                    let fo = format!("{}: {}", fnm.unraw(), spec(fnum));
                     fmt.push(quote!(#fo));
                 }
                 set = quote!({#(#ids: #get),*});
@@ -223,7 +371,8 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
                     if fnum > 0 {
                         fmt.push(quote!(", "));
                     }
-                    fmt.push(quote!("{}"));
+                    let fo = spec(fnum);
+                    fmt.push(quote!(#fo));
                 }
                 set = quote!((#(#get),*));
             }
@@ -240,31 +389,20 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
         }
     });
 
-    let froms = variants.iter().filter_map(|v| {
-        if !v.from {
-            return None;
-        }
-        let syn::Fields::Unnamed(ref fields) = v.fields else {
-            panic!("automatically deriving From is only supported for unnamed fields")
-        };
-        let [ref field] = fields.unnamed.iter().collect::<Vec<_>>()[..] else {
-            panic!("automatically deriving From is only supported with a single field")
-        };
-        let name = &v.ident;
-
-        Some(quote! {
+    let cold = cold.then(|| quote!(#[cold]));
+    let froms = generate_froms(&ident, &generics, &variants);
+    let code = generate_code(&ident, &generics, &variants);
+    let error = (!no_error).then(|| {
+        quote! {
             #[automatically_derived]
-            impl #generics ::core::convert::From<#field> for #ident #generics {
-                fn from(inner: #field) -> Self {
-                    Self::#name(inner)
-                }
-            }
-        })
+            impl #generics ::core::error::Error for #ident #generics {}
+        }
     });
 
     quote! {
         #[automatically_derived]
         impl #generics ::core::fmt::Display for #ident #generics {
+            #cold
             fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
                 match self {
                     #(#arms,)*
@@ -272,10 +410,11 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
             }
         }
 
-        #[automatically_derived]
-        impl #generics ::core::error::Error for #ident #generics {}
+        #error
 
         #(#froms)*
+
+        #code
     }
 }
 
@@ -301,6 +440,9 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
 ///         species: &'a str,
 ///         leggies: u64,
 ///     },
+///     /// codes can be given to map errors to exit codes and such
+///     #[err(code = 3)]
+///     WithCode,
 /// }
 ///
 /// assert_eq!(format!("{}", Error::NoFields), "i am a doc comment");
@@ -317,13 +459,214 @@ fn generate(parsed: ParsedErrors) -> TokenStream {
 ///     "NamedFields: species: fox, leggies: 4",
 /// );
 /// assert_eq!(Error::from("meow"), Error::OneField("meow"));
+/// assert_eq!(Error::WithCode.code(), 3);
+/// // and everything else gets 1
+/// assert_eq!(Error::NoFields.code(), 1);
+/// ```
+///
+/// # messages from elsewhere
+/// besides a string, `msg` can be a path or function call, for sharing a
+/// message between variants. it gets evaluated whenever the error is
+/// formatted, so it only needs to implement `Display`. either way the
+/// message is written as-is, braces in it are not format placeholders
+/// ```rust
+/// const NOPE: &str = "not allowed";
+///
+/// fn shouting() -> String {
+///     NOPE.to_uppercase()
+/// }
+///
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     #[err(msg = NOPE)]
+///     Read(u8),
+///     #[err(msg = Error::WRITE)]
+///     Write { path: &'static str },
+///     #[err(msg = shouting())]
+///     Loud,
+///     #[err(msg = "{braces} stay")]
+///     Braces,
+/// }
+///
+/// impl Error {
+///     const WRITE: &str = "cannot write";
+/// }
+///
+/// assert_eq!(format!("{}", Error::Read(2)), "not allowed: 2");
+/// assert_eq!(
+///     format!("{}", Error::Write { path: "/meow" }),
+///     "cannot write: path: /meow",
+/// );
+/// assert_eq!(format!("{}", Error::Loud), "NOT ALLOWED");
+/// assert_eq!(format!("{}", Error::Braces), "{braces} stay");
+/// ```
+///
+/// # raw identifiers
+/// the `r#` is left out of messages
+/// ```rust
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     NamedFields { r#type: u8 },
+///     r#Loop,
+/// }
+///
+/// assert_eq!(
+///     format!("{}", Error::NamedFields { r#type: 2 }),
+///     "NamedFields: type: 2",
+/// );
+/// assert_eq!(format!("{}", Error::r#Loop), "Loop");
+/// ```
+///
+/// # from with a named field
+/// a variant with a single named field can also get a `From`
+/// ```rust
+/// #[derive(Debug, PartialEq, foxerror::FoxError)]
+/// enum Error {
+///     #[err(from)]
+///     Parse { source: std::num::ParseIntError },
+/// }
+///
+/// let source = "meow".parse::<u8>().unwrap_err();
+/// assert_eq!(Error::from(source.clone()), Error::Parse { source });
+/// ```
+///
+/// but more than one field is an error
+/// ```compile_fail
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     #[err(from)]
+///     Parse { source: std::num::ParseIntError, line: usize },
+/// }
+/// ```
+///
+/// # debug fields
+/// fields are formatted with `Display` by default. `#[err(debug)]` on a
+/// field uses `Debug` for it instead, for types like `PathBuf` that do
+/// not implement `Display`. on a variant it applies to all of its
+/// fields
+/// ```rust
+/// use std::path::PathBuf;
+///
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     /// could not open
+///     Open {
+///         #[err(debug)]
+///         path: PathBuf,
+///         tries: u8,
+///     },
+///     /// not a directory
+///     #[err(debug)]
+///     NotDir(PathBuf, Option<u8>),
+/// }
+///
+/// assert_eq!(
+///     format!("{}", Error::Open { path: "/meow".into(), tries: 3 }),
+///     "could not open: path: \"/meow\", tries: 3",
+/// );
+/// assert_eq!(
+///     format!("{}", Error::NotDir("/mrrp".into(), None)),
+///     "not a directory: \"/mrrp\", None",
+/// );
+/// ```
+///
+/// # discriminants
+/// explicit discriminants are left alone. when a `code()` method is
+/// generated, variants without a code of their own use their
+/// discriminant
+/// ```rust
+/// #[derive(Debug, foxerror::FoxError)]
+/// #[repr(u16)]
+/// enum Error {
+///     /// not found
+///     NotFound = 404,
+///     /// too many requests
+///     Slow { retry: u8 } = 429,
+///     #[err(code = 2)]
+///     Teapot = 418,
+/// }
+///
+/// assert_eq!(format!("{}", Error::NotFound), "not found");
+/// assert_eq!(
+///     format!("{}", Error::Slow { retry: 3 }),
+///     "too many requests: retry: 3",
+/// );
+/// assert_eq!(Error::NotFound.code(), 404);
+/// assert_eq!(Error::Slow { retry: 3 }.code(), 429);
+/// assert_eq!(Error::Teapot.code(), 2);
+/// ```
+///
+/// # without the error impl
+/// `#[err(no_error)]` on the enum itself only generates `Display` (and
+/// any `From`s), for when you want to implement `Error` yourself.
+/// anything that would only affect the `Error` impl, like `source()`,
+/// is ignored
+/// ```rust
+/// #[derive(Debug, foxerror::FoxError)]
+/// #[err(no_error)]
+/// enum Error {
+///     /// something went wrong
+///     Oops,
+/// }
+///
+/// // would conflict without no_error
+/// impl std::error::Error for Error {}
+///
+/// assert_eq!(format!("{}", Error::Oops), "something went wrong");
+/// ```
+///
+/// # cold
+/// errors are usually formatted rarely, if ever, so `#[err(cold)]` on
+/// the enum marks the `Display` impl's `fmt` as `#[cold]`. this hints to
+/// the optimizer that code leading to it is unlikely, so it can keep
+/// the happy path tight. the generated `From` impls are always
+/// `#[inline]`, since they only wrap a value
+/// ```rust
+/// #![deny(warnings)]
+///
+/// #[derive(Debug, foxerror::FoxError)]
+/// #[err(cold)]
+/// enum Error {
+///     /// could not parse
+///     #[err(from)]
+///     Parse(std::num::ParseIntError),
+/// }
+///
+/// let e = Error::from("meow".parse::<u8>().unwrap_err());
+/// assert_eq!(format!("{e}"), "could not parse: invalid digit found in string");
+/// ```
+///
+/// # mistakes
+/// malformed attributes are compile errors pointing at the problem
+/// ```compile_fail
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     #[err(msg = "unclosed" + )]
+///     Oops,
+/// }
+/// ```
+///
+/// as are unknown ones
+/// ```compile_fail
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     #[err(mgs = "typo")]
+///     Oops,
+/// }
+/// ```
+///
+/// and messages that are not strings
+/// ```compile_fail
+/// #[derive(Debug, foxerror::FoxError)]
+/// enum Error {
+///     #[err(msg = 5)]
+///     Oops,
+/// }
 /// ```
-#[allow(clippy::missing_panics_doc)]
 #[proc_macro_derive(FoxError, attributes(err))]
 pub fn foxerror(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input = syn::parse(input).expect("input should be parseable");
-    let parsed = parse_derive(input);
-    let output = generate(parsed);
-
-    output.into()
+    syn::parse(input)
+        .and_then(parse_derive)
+        .map_or_else(syn::Error::into_compile_error, generate)
+        .into()
 }
@@ -2,64 +2,338 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use argh::{FromArgs, from_env};
 use axum::{
     Json, Router,
     body::{Body, Bytes},
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    extract::{
+        Path, Query, Request, State,
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+    },
+    http::{
+        HeaderMap, StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware::{self, Next},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
+use base64::prelude::{BASE64_STANDARD, Engine as _};
 use irc_connect::tokio_rustls::rustls::{
     RootCertStore,
-    pki_types::{CertificateDer, pem::PemObject},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    convert::Infallible,
+    fmt::Write as _,
     hash::Hasher,
     net::SocketAddr,
+    path::PathBuf,
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
     },
+    time::Duration,
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::TcpListener,
-    sync::{RwLock, broadcast, mpsc},
+    sync::{Mutex, Notify, RwLock, broadcast, mpsc, oneshot, watch},
     task::AbortHandle,
+    time::Instant,
 };
+use tokio_stream::wrappers::ReceiverStream;
+
+/// irc puppeteering over http
+#[derive(Debug, FromArgs)]
+struct Opt {
+    /// seconds to let a running job finish when shutting down
+    #[argh(option, default = "5")]
+    grace_period: u64,
+    /// token clients must send as `Authorization: Bearer <token>`, or
+    /// as `?token=<token>` for websockets, defaults to the `GEKKER_TOKEN`
+    /// environment variable. only `/status` and the dashboard are left
+    /// open
+    #[argh(option)]
+    token: Option<String>,
+    /// most lines per second each client may send, unlimited by default
+    #[argh(option)]
+    rate: Option<f64>,
+    /// how many lines each client may send at once before --rate kicks in
+    #[argh(option, default = "5")]
+    burst: u32,
+    /// address to listen on, defaults to [::]:8667
+    #[argh(positional)]
+    listen: Option<SocketAddr>,
+}
+
+/// how many messages to keep around for each client
+const MESSAGE_BUFFER: usize = 256;
+/// how long to wait before the first reconnection attempt, doubling
+/// after each failure
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+/// the most to wait between reconnection attempts
+const RECONNECT_MAX: Duration = Duration::from_mins(5);
+
+#[derive(Debug, Clone, Serialize)]
+struct Message {
+    index: u64,
+    source: Option<String>,
+    command: String,
+    target: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct MessageLog {
+    /// the index the next message will get, and the recent messages
+    inner: Mutex<(u64, VecDeque<Message>)>,
+    new_message: Notify,
+}
+
+impl MessageLog {
+    async fn push(&self, line: &irctokens::Line) {
+        let lossy = |b: &[u8]| String::from_utf8_lossy(b).into_owned();
+        {
+            let mut guard = self.inner.lock().await;
+            let (next, messages) = &mut *guard;
+            if messages.len() >= MESSAGE_BUFFER {
+                messages.pop_front();
+            }
+            messages.push_back(Message {
+                index: *next,
+                source: line.source.as_deref().map(lossy),
+                command: line.command.clone(),
+                target: line.arguments.first().map(|a| lossy(a)),
+                text: line
+                    .arguments
+                    .get(1..)
+                    .and_then(<[_]>::last)
+                    .map(|a| lossy(a)),
+            });
+            *next += 1;
+        }
+        self.new_message.notify_waiters();
+    }
+
+    async fn since(&self, since: u64) -> (u64, Vec<Message>) {
+        let guard = self.inner.lock().await;
+        let (next, messages) = &*guard;
+        (
+            *next,
+            messages
+                .iter()
+                .filter(|m| m.index >= since)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// a line for a client to send
+#[derive(Debug)]
+struct Outgoing {
+    line: Vec<u8>,
+    /// told once the line has been written. if the receiving end is
+    /// gone, nobody is waiting on it anymore and the line gets dropped
+    written: Option<oneshot::Sender<()>>,
+}
+
+impl From<Vec<u8>> for Outgoing {
+    fn from(line: Vec<u8>) -> Self {
+        Self {
+            line,
+            written: None,
+        }
+    }
+}
+
+impl Outgoing {
+    /// a line along with a way to wait for it to be written
+    fn tracked(line: Vec<u8>) -> (Self, oneshot::Receiver<()>) {
+        let (tx, rx) = oneshot::channel();
+        let out = Self {
+            line,
+            written: Some(tx),
+        };
+        (out, rx)
+    }
+}
+
+/// queue a line and wait for it to actually be written, giving back
+/// whether it was
+async fn send_tracked(sender: &mpsc::Sender<Outgoing>, line: Vec<u8>) -> bool {
+    let (out, written) = Outgoing::tracked(line);
+    sender.send(out).await.is_ok() && written.await.is_ok()
+}
+
+#[tokio::test]
+async fn tracked_waits_for_write() {
+    let (sender, mut receiver) = mpsc::channel(1);
+    let writer = tokio::spawn(async move {
+        let out: Outgoing = receiver.recv().await.unwrap();
+        assert_eq!(out.line, b"PING :a");
+        out.written.unwrap().send(()).unwrap();
+        // dropped without being written
+        receiver.recv().await.unwrap();
+    });
+    assert!(send_tracked(&sender, b"PING :a".to_vec()).await);
+    assert!(!send_tracked(&sender, b"PING :b".to_vec()).await);
+    writer.await.unwrap();
+}
 
 #[derive(Debug)]
 struct Client {
     nick: RwLock<Option<Vec<u8>>>,
-    sender: mpsc::Sender<Vec<u8>>,
+    sender: mpsc::Sender<Outgoing>,
     raw_feed: broadcast::Sender<Bytes>,
     hash_feed: broadcast::Sender<u64>,
+    messages: Arc<MessageLog>,
 }
 
 #[derive(Debug)]
 struct AppState {
     clients: RwLock<Vec<Option<Client>>>,
     active: RwLock<BTreeSet<usize>>,
-    autojoin: RwLock<Option<String>>,
+    /// channels to join after registering, and their keys
+    autojoin: RwLock<Vec<(String, Option<String>)>>,
     job: RwLock<AbortHandle>,
     job_sent: AtomicUsize,
     job_total: AtomicUsize,
+    job_progress: watch::Sender<JobProgress>,
+    /// becomes true once shutdown starts, ending streams that would
+    /// otherwise keep the server from draining
+    shutting_down: watch::Sender<bool>,
+    /// slots waiting to reconnect, and how long they are waiting for
+    reconnecting: RwLock<BTreeMap<usize, Duration>>,
     ca_certs: Arc<RootCertStore>,
+    token: Option<String>,
+    /// lines per second and burst size each client is limited to
+    rate: Option<(f64, u32)>,
+}
+
+impl AppState {
+    /// must be called from within the runtime, as it needs a placeholder
+    /// job to start with
+    fn new(ca_certs: RootCertStore, token: Option<String>, rate: Option<(f64, u32)>) -> Self {
+        Self {
+            clients: RwLock::new(vec![]),
+            active: RwLock::new(BTreeSet::new()),
+            autojoin: RwLock::new(Vec::new()),
+            job: RwLock::new(tokio::spawn(async {}).abort_handle()),
+            job_sent: AtomicUsize::new(0),
+            job_total: AtomicUsize::new(0),
+            job_progress: watch::Sender::new(JobProgress {
+                state: JobState::Finished,
+                sent: 0,
+                total: 0,
+            }),
+            shutting_down: watch::Sender::new(false),
+            reconnecting: RwLock::new(BTreeMap::new()),
+            ca_certs: Arc::new(ca_certs),
+            token,
+            rate,
+        }
+    }
+
+    /// count another line of the job as sent
+    fn job_step(&self) {
+        let sent = self.job_sent.fetch_add(1, Ordering::SeqCst) + 1;
+        self.job_progress.send_modify(|p| p.sent = sent);
+    }
+}
+
+/// paces outgoing lines, letting a few through at once
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new((rate, burst): (f64, u32)) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refilled = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refilled).min(self.burst);
+        self.last = now;
+    }
+
+    /// when the next line may be sent, or None if it may be sent now
+    fn ready_at(&mut self) -> Option<Instant> {
+        self.refill();
+        (self.tokens < 1.0)
+            .then(|| self.last + Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+
+    fn take(&mut self) {
+        // time spent waiting for the line must not count towards the
+        // next one
+        self.refill();
+        self.tokens -= 1.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum JobState {
+    Running,
+    Finished,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct JobProgress {
+    state: JobState,
+    sent: usize,
+    total: usize,
+}
+
+/// marks the job as over when dropped, which also happens when it gets
+/// aborted
+struct JobDone {
+    state: Arc<AppState>,
+    finished: bool,
+}
+
+impl Drop for JobDone {
+    fn drop(&mut self) {
+        let end = if self.finished {
+            JobState::Finished
+        } else {
+            JobState::Cancelled
+        };
+        self.state.job_progress.send_modify(|p| p.state = end);
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct StatusClient {
     nick: String,
     active: bool,
+    reconnecting: bool,
 }
 
 #[derive(Debug, Serialize)]
 struct StatusReply {
     clients: Vec<Option<StatusClient>>,
-    autojoin: Option<String>,
+    /// keys are left out, since status needs no token
+    autojoin: Vec<String>,
     job_active: bool,
     job_sent: usize,
     job_total: usize,
@@ -70,6 +344,7 @@ async fn status(State(state): State<Arc<AppState>>) -> Json<StatusReply> {
     {
         let clients_handle = state.clients.read().await;
         let active = state.active.read().await;
+        let reconnecting = state.reconnecting.read().await;
         for (n, client) in clients_handle.iter().enumerate() {
             clients.push(if let Some(client) = client {
                 Some(StatusClient {
@@ -82,13 +357,20 @@ async fn status(State(state): State<Arc<AppState>>) -> Json<StatusReply> {
                         .unwrap_or("???")
                         .to_string(),
                     active: active.contains(&n),
+                    reconnecting: reconnecting.contains_key(&n),
                 })
             } else {
                 None
             });
         }
     }
-    let autojoin = state.autojoin.read().await.clone();
+    let autojoin = state
+        .autojoin
+        .read()
+        .await
+        .iter()
+        .map(|(channel, _)| channel.clone())
+        .collect();
     let job_active = !state.job.read().await.is_finished();
     let job_sent = state.job_sent.load(Ordering::SeqCst);
     let job_total = state.job_total.load(Ordering::SeqCst);
@@ -101,6 +383,36 @@ async fn status(State(state): State<Arc<AppState>>) -> Json<StatusReply> {
     })
 }
 
+/// the numbers from the status in prometheus' text format
+async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let clients = state.clients.read().await.iter().flatten().count();
+    let active = state.active.read().await.len();
+    let reconnecting = state.reconnecting.read().await.len();
+    let job_active = u8::from(!state.job.read().await.is_finished());
+    let job_sent = state.job_sent.load(Ordering::SeqCst);
+    let job_total = state.job_total.load(Ordering::SeqCst);
+
+    let mut out = String::new();
+    for (name, help, value) in [
+        ("clients", "clients with a slot", clients),
+        ("clients_active", "clients that will be sent lines", active),
+        (
+            "clients_reconnecting",
+            "clients waiting to reconnect",
+            reconnecting,
+        ),
+        ("job_active", "whether a job is running", job_active.into()),
+        ("job_sent", "lines sent by the current job", job_sent),
+        ("job_total", "lines in the current job", job_total),
+    ] {
+        _ = write!(
+            out,
+            "# HELP gekker_{name} {help}\n# TYPE gekker_{name} gauge\ngekker_{name} {value}\n"
+        );
+    }
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
 fn hash_line(nick: &[u8], command: &str, trail: &[u8]) -> u64 {
     let mut hasher = std::hash::DefaultHasher::new();
     hasher.write(nick);
@@ -114,24 +426,133 @@ fn hash_line(nick: &[u8], command: &str, trail: &[u8]) -> u64 {
     hasher.finish()
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct ConnectArgs {
     nick: String,
     user: Option<String>,
     gecos: Option<String>,
-    host: String,
+    /// server to connect to over tcp, or with `ipc_path` the tls
+    /// servername to expect
+    host: Option<String>,
+    /// unix socket to connect to instead of a host
+    ipc_path: Option<PathBuf>,
     socks5: Option<SocketAddr>,
     #[serde(default)]
     plaintext: bool,
     #[serde(default)]
     insecure: bool,
+    /// tls client certificate chain, either a path or inline pem
+    cert: Option<String>,
+    /// private key for the client certificate, either a path or inline pem
+    key: Option<String>,
+    /// account to log in to with sasl plain
+    sasl_user: Option<String>,
+    sasl_pass: Option<String>,
+    /// connect again with backoff if the server drops us
+    #[serde(default)]
+    reconnect: bool,
 }
 
-async fn connect(
-    State(state): State<Arc<AppState>>,
-    Query(args): Query<ConnectArgs>,
-) -> Result<(), (StatusCode, String)> {
-    let conn = irc_connect::Connection::new_tcp(args.host);
+fn is_inline_pem(s: &str) -> bool {
+    s.trim_start().starts_with("-----BEGIN")
+}
+
+fn load_client_cert(
+    cert: &str,
+    key: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), String> {
+    let chain = if is_inline_pem(cert) {
+        CertificateDer::pem_slice_iter(cert.as_bytes()).collect::<Result<Vec<_>, _>>()
+    } else {
+        CertificateDer::pem_file_iter(cert).and_then(Iterator::collect)
+    }
+    .map_err(|e| format!("could not load cert: {e}"))?;
+    if chain.is_empty() {
+        return Err("could not load cert: no certificates found".to_string());
+    }
+    let key = if is_inline_pem(key) {
+        PrivateKeyDer::from_pem_slice(key.as_bytes())
+    } else {
+        PrivateKeyDer::from_pem_file(key)
+    }
+    .map_err(|e| format!("could not load key: {e}"))?;
+    Ok((chain, key))
+}
+
+/// check that `s` can be sent as one irc parameter that is not the
+/// last, so it cannot end the line early or add more parameters
+fn is_middle_param(s: &str) -> bool {
+    !s.is_empty() && !s.starts_with(':') && !s.contains([' ', '\r', '\n', '\0'])
+}
+
+/// check that `s` can be the last irc parameter without ending the line
+fn is_trailing_param(s: &str) -> bool {
+    !s.contains(['\r', '\n', '\0'])
+}
+
+/// check the parts of the registration lines that come from the query
+fn check_registration(
+    nick: &str,
+    user: Option<&str>,
+    gecos: Option<&str>,
+) -> Result<(), &'static str> {
+    if !is_middle_param(nick) {
+        return Err("invalid nick");
+    }
+    if user.is_some_and(|u| !is_middle_param(u)) {
+        return Err("invalid user");
+    }
+    if gecos.is_some_and(|g| !is_trailing_param(g)) {
+        return Err("invalid gecos");
+    }
+    Ok(())
+}
+
+#[test]
+fn registration() {
+    assert_eq!(check_registration("meow", None, Some("a :b")), Ok(()));
+    assert_eq!(
+        check_registration("meow\r\nQUIT", None, None),
+        Err("invalid nick")
+    );
+    assert_eq!(check_registration("me ow", None, None), Err("invalid nick"));
+    assert_eq!(check_registration("", None, None), Err("invalid nick"));
+    assert_eq!(
+        check_registration("meow", Some(":x"), None),
+        Err("invalid user")
+    );
+    assert_eq!(
+        check_registration("meow", None, Some("a\0b")),
+        Err("invalid gecos")
+    );
+}
+
+/// the sasl lines to send once the server acks the cap
+type Sasl = Option<Vec<Vec<u8>>>;
+
+/// open a connection and start registering
+async fn dial(
+    state: &AppState,
+    args: &ConnectArgs,
+) -> Result<(irc_connect::Connection, Sasl), (StatusCode, String)> {
+    let bad = |e: &str| (StatusCode::BAD_REQUEST, e.to_string());
+    check_registration(&args.nick, args.user.as_deref(), args.gecos.as_deref()).map_err(bad)?;
+    // tcp targets get their servername guessed from the host
+    let (conn, domain) = match (&args.ipc_path, &args.host) {
+        (Some(_), _) if args.socks5.is_some() => {
+            return Err(bad("socks5 cannot be used with ipc_path"));
+        }
+        (Some(path), host) => {
+            let domain = host
+                .as_ref()
+                .map(|h| ServerName::try_from(h.clone()))
+                .transpose()
+                .map_err(|_| bad("host is not a valid servername"))?;
+            (irc_connect::Connection::new_unix(path), domain)
+        }
+        (None, Some(host)) => (irc_connect::Connection::new_tcp(host.as_str()), None),
+        (None, None) => return Err(bad("host or ipc_path is required")),
+    };
     let conn = if let Some(addr) = args.socks5 {
         conn.socks5(addr)
     } else {
@@ -140,16 +561,43 @@ async fn connect(
     let conn = if args.plaintext {
         conn
     } else if args.insecure {
-        conn.tls_danger_insecure(None)
+        conn.tls_danger_insecure(domain)
     } else {
-        conn.tls_with_root(None, state.ca_certs.clone())
+        conn.tls_with_root(domain, state.ca_certs.clone())
+    };
+    let conn = match (&args.cert, &args.key) {
+        (Some(cert), Some(key)) => {
+            let (chain, key) =
+                load_client_cert(cert, key).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            conn.client_cert(chain, key)
+        }
+        (None, None) => conn,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "cert and key must be given together".to_string(),
+            ));
+        }
+    };
+    let sasl = match (&args.sasl_user, &args.sasl_pass) {
+        (Some(user), Some(pass)) => Some(sasl_plain(user, pass)),
+        (None, None) => None,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "sasl_user and sasl_pass must be given together".to_string(),
+            ));
+        }
     };
     let mut conn = conn
         .connect()
         .await
         .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
-    let slot_info = reserve_client_slot(&state.clients).await;
-    let slot = slot_info.slot;
+    if sasl.is_some() {
+        conn.write_all(b"CAP REQ :sasl\r\n")
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
     conn.write_all(
         format!(
             "NICK {}\r\nUSER {} 0 * :{}\r\n",
@@ -164,10 +612,20 @@ async fn connect(
     conn.flush()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((conn, sasl))
+}
+
+async fn connect(
+    State(state): State<Arc<AppState>>,
+    Query(args): Query<ConnectArgs>,
+) -> Result<(), (StatusCode, String)> {
+    let (conn, sasl) = dial(&state, &args).await?;
+    let slot_info = reserve_client_slot(&state.clients).await;
+    let slot = slot_info.slot;
     tokio::spawn(async move {
         let state_ = state.clone();
         _ = tokio::spawn(async move {
-            client_loop(state_, conn, slot_info).await;
+            run_client(state_, args, conn, slot_info, sasl).await;
         })
         .await;
 
@@ -175,26 +633,92 @@ async fn connect(
         let mut active = state.active.write().await;
         clients[slot] = None;
         active.remove(&slot);
+        state.reconnecting.write().await.remove(&slot);
     });
     Ok(())
 }
 
+/// keep a client going in its slot, reconnecting when asked to until
+/// it quits on purpose
+async fn run_client(
+    state: Arc<AppState>,
+    args: ConnectArgs,
+    mut conn: irc_connect::Connection,
+    mut slot_info: SlotInfo,
+    mut sasl: Sasl,
+) {
+    let slot = slot_info.slot;
+    let mut delay = RECONNECT_MIN;
+    loop {
+        match client_loop(state.clone(), conn, &mut slot_info, sasl).await {
+            Ended::Quit => return,
+            Ended::Dropped { .. } if !args.reconnect => return,
+            Ended::Dropped { registered } => {
+                if registered {
+                    delay = RECONNECT_MIN;
+                }
+            }
+        }
+        state.active.write().await.remove(&slot);
+        (conn, sasl) = loop {
+            state.reconnecting.write().await.insert(slot, delay);
+            let Some(result) = redial(&state, &args, &mut slot_info.receiver, delay).await else {
+                return;
+            };
+            delay = (delay * 2).min(RECONNECT_MAX);
+            match result {
+                Ok(dialed) => break dialed,
+                Err((_, e)) => println!("slot {slot} could not reconnect: {e}"),
+            }
+        };
+        state.reconnecting.write().await.remove(&slot);
+    }
+}
+
+/// wait out the backoff and then dial again, giving up if a QUIT comes
+/// in meanwhile so that disconnecting does not race the reconnection
+async fn redial(
+    state: &AppState,
+    args: &ConnectArgs,
+    receiver: &mut mpsc::Receiver<Outgoing>,
+    delay: Duration,
+) -> Option<Result<(irc_connect::Connection, Sasl), (StatusCode, String)>> {
+    let attempt = async {
+        tokio::time::sleep(delay).await;
+        dial(state, args).await
+    };
+    tokio::pin!(attempt);
+    loop {
+        tokio::select! {
+            result = &mut attempt => return Some(result),
+            line = receiver.recv() => match line {
+                // there is nowhere to send anything else
+                Some(out) if !is_quit(&out.line) => (),
+                _ => return None,
+            },
+        }
+    }
+}
+
 struct SlotInfo {
     slot: usize,
-    receiver: mpsc::Receiver<Vec<u8>>,
+    receiver: mpsc::Receiver<Outgoing>,
     raw_feed: broadcast::Sender<Bytes>,
     hash_feed: broadcast::Sender<u64>,
+    messages: Arc<MessageLog>,
 }
 
 async fn reserve_client_slot(clients: &RwLock<Vec<Option<Client>>>) -> SlotInfo {
     let (sender, receiver) = mpsc::channel(6);
     let raw_feed = broadcast::channel(32).0;
     let hash_feed = broadcast::channel(32).0;
+    let messages = Arc::new(MessageLog::default());
     let client = Client {
         nick: RwLock::new(None),
         sender,
         raw_feed: raw_feed.clone(),
         hash_feed: hash_feed.clone(),
+        messages: messages.clone(),
     };
     let mut clients = clients.write().await;
     let slot = clients.iter().position(Option::is_none).unwrap_or_else(|| {
@@ -209,29 +733,87 @@ async fn reserve_client_slot(clients: &RwLock<Vec<Option<Client>>>) -> SlotInfo
         receiver,
         raw_feed,
         hash_feed,
+        messages,
+    }
+}
+
+/// the AUTHENTICATE lines for logging in with sasl plain, split up
+/// into 400 byte chunks
+fn sasl_plain(user: &str, pass: &str) -> Vec<Vec<u8>> {
+    let encoded = BASE64_STANDARD.encode(format!("{user}\0{user}\0{pass}"));
+    let mut lines: Vec<_> = encoded
+        .as_bytes()
+        .chunks(400)
+        .map(|c| [b"AUTHENTICATE ", c].concat())
+        .collect();
+    // an exactly 400 byte chunk means there is more to come
+    if encoded.len() % 400 == 0 {
+        lines.push(b"AUTHENTICATE +".to_vec());
     }
+    lines
+}
+
+/// send a line to the server, giving back false if that did not work
+async fn send_line(write: &mut (impl AsyncWrite + Unpin), line: &[u8]) -> bool {
+    let line = [line, b"\r\n"].concat();
+    write.write_all(&line).await.is_ok() && write.flush().await.is_ok()
+}
+
+fn is_quit(line: &[u8]) -> bool {
+    line.split(|&b| b == b' ')
+        .next()
+        .is_some_and(|c| c.eq_ignore_ascii_case(b"QUIT"))
 }
 
-async fn client_loop(state: Arc<AppState>, conn: irc_connect::Connection, slot_info: SlotInfo) {
+/// why [`client_loop`] stopped
+enum Ended {
+    /// we quit, or gave up on the connection
+    Quit,
+    /// the connection went away on its own
+    Dropped {
+        /// whether registration finished first
+        registered: bool,
+    },
+}
+
+#[allow(clippy::too_many_lines)]
+async fn client_loop(
+    state: Arc<AppState>,
+    conn: irc_connect::Connection,
+    slot_info: &mut SlotInfo,
+    mut sasl: Sasl,
+) -> Ended {
     let SlotInfo {
         slot,
-        mut receiver,
-        raw_feed,
-        hash_feed,
-    } = slot_info;
+        ref mut receiver,
+        ref raw_feed,
+        ref hash_feed,
+        ref messages,
+    } = *slot_info;
+    let mut registered = false;
+    // set until the server says we are logged in, registering before
+    // then means it skipped sasl entirely
+    let mut logging_in = sasl.is_some();
+    // autojoin channels we are still waiting on, the client becomes
+    // active once all of them are joined or refused
+    let mut pending: Vec<Vec<u8>> = Vec::new();
+    let mut joined = false;
+    let dropped = |registered| Ended::Dropped { registered };
     let (read, mut write) = tokio::io::split(conn);
     let mut read = BufReader::new(read);
     let mut ircbuf = Vec::with_capacity(512);
+    let mut bucket = state.rate.map(TokenBucket::new);
     loop {
+        let wait = bucket.as_mut().and_then(TokenBucket::ready_at);
         tokio::select! {
-            Ok(len) = read.read_until(b'\n', &mut ircbuf) => {
-                if len == 0 {
-                    return;
+            len = read.read_until(b'\n', &mut ircbuf) => {
+                if !len.is_ok_and(|len| len > 0) {
+                    return dropped(registered);
                 }
                 _ = raw_feed.send(Bytes::copy_from_slice(&ircbuf));
                 while ircbuf.pop_if(|c| b"\r\n".contains(c)).is_some() {}
                 let Ok(mut line) = irctokens::Line::tokenise(&ircbuf) else {
-                    return;
+                    return dropped(registered);
                 };
                 line.command.make_ascii_uppercase();
                 let line = line;
@@ -254,70 +836,178 @@ async fn client_loop(state: Arc<AppState>, conn: irc_connect::Connection, slot_i
                         let mut out = out.format();
                         out.extend_from_slice(b"\r\n");
                         if write.write_all(&out).await.is_err() {
-                            return;
+                            return dropped(registered);
                         }
                         if write.flush().await.is_err() {
-                            return;
+                            return dropped(registered);
                         }
                     }
                     "NICK" if { source_nick == state.clients.read().await[slot].as_ref().unwrap().nick.read().await.as_deref() } => {
                         let clients = state.clients.read().await;
                         *clients[slot].as_ref().unwrap().nick.write().await = line.arguments.first().cloned();
                     }
+                    "001" if logging_in => {
+                        _ = send_line(&mut write, b"QUIT :registered without sasl").await;
+                        return Ended::Quit;
+                    }
                     "001" => {
+                        registered = true;
                         {
                             let clients = state.clients.read().await;
                             *clients[slot].as_ref().unwrap().nick.write().await = line.arguments.first().cloned();
                         }
-                        if let Some(channel) = state.autojoin.read().await.as_ref() {
+                        let autojoin = state.autojoin.read().await.clone();
+                        pending = autojoin.iter().map(|(channel, _)| channel.to_ascii_lowercase().into_bytes()).collect();
+                        joined = false;
+                        // one JOIN each, so a long list cannot go over
+                        // the line length limit
+                        for (channel, key) in autojoin {
                             let out = irctokens::Line {
                                 tags: None,
                                 source: None,
                                 command: "JOIN".to_string(),
-                                arguments: vec![channel.as_bytes().to_vec()],
+                                arguments: [Some(channel), key].into_iter().flatten().map(String::into_bytes).collect(),
                             };
-                            let mut out = out.format();
-                            out.extend_from_slice(b"\r\n");
-                            if write.write_all(&out).await.is_err() {
-                                return;
-                            }
-                            if write.flush().await.is_err() {
-                                return;
+                            if !send_line(&mut write, &out.format()).await {
+                                return dropped(registered);
                             }
                         }
                     }
                     "366" => {
-                        state.active.write().await.insert(slot);
+                        joined = true;
+                        if let Some(channel) = line.arguments.get(1) {
+                            pending.retain(|p| !p.eq_ignore_ascii_case(channel));
+                        }
+                        if pending.is_empty() {
+                            state.active.write().await.insert(slot);
+                        }
+                    }
+                    // could not join, so stop waiting for it
+                    "403" | "405" | "471" | "473" | "474" | "475" => {
+                        if let Some(channel) = line.arguments.get(1) {
+                            pending.retain(|p| !p.eq_ignore_ascii_case(channel));
+                        }
+                        if joined && pending.is_empty() {
+                            state.active.write().await.insert(slot);
+                        }
+                    }
+                    "CAP" if sasl.is_some() => {
+                        let acked = match line.arguments.get(1).map(Vec::as_slice) {
+                            Some(b"ACK") => line.arguments.last().is_some_and(|caps| {
+                                caps.split(|&b| b == b' ').any(|c| c == b"sasl")
+                            }),
+                            Some(b"NAK") => false,
+                            _ => continue,
+                        };
+                        if !acked {
+                            _ = send_line(&mut write, b"QUIT :server does not support sasl").await;
+                            return Ended::Quit;
+                        }
+                        if !send_line(&mut write, b"AUTHENTICATE PLAIN").await {
+                            return dropped(registered);
+                        }
+                    }
+                    "AUTHENTICATE" if line.arguments.first().is_some_and(|a| a == b"+") => {
+                        for auth in sasl.take().into_iter().flatten() {
+                            if !send_line(&mut write, &auth).await {
+                                return dropped(registered);
+                            }
+                        }
+                    }
+                    "903" => {
+                        // logged in, so registration can go on
+                        logging_in = false;
+                        let ended = send_line(&mut write, b"CAP END").await;
+                        if !ended {
+                            return dropped(registered);
+                        }
                     }
+                    // nick locked, failed, too long, aborted, or the
+                    // server does not do plain
+                    "902" | "904" | "905" | "906" | "908" if logging_in => {
+                        _ = send_line(&mut write, b"QUIT :sasl authentication failed").await;
+                        return Ended::Quit;
+                    }
+                    "PRIVMSG" | "NOTICE" => messages.push(&line).await,
                     _ => (),
                 }
             }
-            Some(mut line) = receiver.recv() => {
+            () = tokio::time::sleep_until(wait.unwrap_or_else(Instant::now)), if wait.is_some() => {}
+            Some(Outgoing { mut line, written }) = receiver.recv(), if wait.is_none() => {
+                // the job that queued it was cancelled
+                if written.as_ref().is_some_and(oneshot::Sender::is_closed) {
+                    continue;
+                }
+                if let Some(bucket) = &mut bucket {
+                    bucket.take();
+                }
+                let is_quit = is_quit(&line);
                 line.extend_from_slice(b"\r\n");
-                if write.write_all(&line).await.is_err() {
-                    return;
+                let sent = write.write_all(&line).await.is_ok() && write.flush().await.is_ok();
+                if sent && let Some(written) = written {
+                    _ = written.send(());
                 }
-                if write.flush().await.is_err() {
-                    return;
+                // no need to wait around for the server to hang up on
+                // us, and a failed QUIT still means we meant to leave
+                if is_quit {
+                    return Ended::Quit;
+                }
+                if !sent {
+                    return dropped(registered);
                 }
             }
             else => {
-                return;
+                return Ended::Quit;
             }
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
-struct MaybeChannel {
+struct AutojoinQuery {
+    /// comma separated, like JOIN
     channel: Option<String>,
+    /// comma separated, matched up with channels in order
+    key: Option<String>,
+}
+
+/// split up comma separated channels and their keys, an empty key
+/// means that channel has none
+fn parse_autojoin(
+    channels: &str,
+    keys: Option<&str>,
+) -> Result<Vec<(String, Option<String>)>, &'static str> {
+    let mut keys = keys.into_iter().flat_map(|k| k.split(','));
+    let mut autojoin = Vec::new();
+    for channel in channels.split(',') {
+        let key = keys.next().filter(|k| !k.is_empty());
+        if !is_middle_param(channel) {
+            return Err("invalid channel name");
+        }
+        if key.is_some_and(|k| !is_middle_param(k)) {
+            return Err("invalid channel key");
+        }
+        autojoin.push((channel.to_string(), key.map(str::to_string)));
+    }
+    if keys.next().is_some() {
+        return Err("more keys than channels");
+    }
+    Ok(autojoin)
 }
 
 async fn set_autojoin(
     State(state): State<Arc<AppState>>,
-    Query(MaybeChannel { channel }): Query<MaybeChannel>,
-) {
-    *state.autojoin.write().await = channel;
+    Query(AutojoinQuery { channel, key }): Query<AutojoinQuery>,
+) -> Result<(), (StatusCode, &'static str)> {
+    let autojoin = match channel {
+        Some(channel) => {
+            parse_autojoin(&channel, key.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?
+        }
+        None if key.is_some() => return Err((StatusCode::BAD_REQUEST, "key without channel")),
+        None => Vec::new(),
+    };
+    *state.autojoin.write().await = autojoin;
+    Ok(())
 }
 
 async fn dispatch_job<F>(
@@ -342,9 +1032,21 @@ where
     }
     state.job_sent.store(0, Ordering::SeqCst);
     state.job_total.store(lines.len(), Ordering::SeqCst);
+    state.job_progress.send_replace(JobProgress {
+        state: JobState::Running,
+        sent: 0,
+        total: lines.len(),
+    });
 
     let state = state.clone();
-    let task = tokio::spawn(async move { callback(state, lines).await });
+    let task = tokio::spawn(async move {
+        let mut done = JobDone {
+            state: state.clone(),
+            finished: false,
+        };
+        callback(state, lines).await;
+        done.finished = true;
+    });
     *job = task.abort_handle();
 
     Ok(())
@@ -356,10 +1058,21 @@ async fn raw_all(
 ) -> Result<(), (StatusCode, &'static str)> {
     dispatch_job(state, body, async |state, lines| {
         for line in lines {
-            for client in state.clients.read().await.iter().flatten() {
-                _ = client.sender.try_send(line.clone());
+            let senders: Vec<_> = (state.clients.read().await.iter().flatten())
+                .map(|c| c.sender.clone())
+                .collect();
+            let mut pending = Vec::new();
+            for sender in senders {
+                let (out, written) = Outgoing::tracked(line.clone());
+                if sender.send(out).await.is_ok() {
+                    pending.push(written);
+                }
             }
-            state.job_sent.fetch_add(1, Ordering::SeqCst);
+            // counted once every client has written it or gone away
+            for written in pending {
+                _ = written.await;
+            }
+            state.job_step();
         }
     })
     .await
@@ -372,13 +1085,20 @@ async fn raw_active(
     dispatch_job(state, body, async |state, lines| {
         for line in lines {
             let active = state.active.read().await.clone();
+            let mut pending = Vec::new();
             for slot in active {
                 let Some(client) = &state.clients.read().await[slot] else {
                     continue;
                 };
-                _ = client.sender.try_send(line.clone());
+                let (out, written) = Outgoing::tracked(line.clone());
+                if client.sender.send(out).await.is_ok() {
+                    pending.push(written);
+                }
+            }
+            for written in pending {
+                _ = written.await;
             }
-            state.job_sent.fetch_add(1, Ordering::SeqCst);
+            state.job_step();
         }
     })
     .await
@@ -391,14 +1111,15 @@ async fn raw_slot(
 ) -> Result<(), (StatusCode, &'static str)> {
     dispatch_job(state, body, async move |state, lines| {
         for line in lines {
-            let clients = state.clients.read().await;
-            let Some(Some(client)) = clients.get(slot) else {
+            let Some(Some(sender)) = (state.clients.read().await.get(slot))
+                .map(|c| c.as_ref().map(|c| c.sender.clone()))
+            else {
                 return;
             };
-            if client.sender.try_send(line).is_err() {
+            if !send_tracked(&sender, line).await {
                 return;
             }
-            state.job_sent.fetch_add(1, Ordering::SeqCst);
+            state.job_step();
         }
     })
     .await
@@ -415,6 +1136,12 @@ async fn send(
     Query(opt): Query<SendOpt>,
     body: Bytes,
 ) -> Result<(), (StatusCode, &'static str)> {
+    if opt.command.as_deref().is_some_and(|c| !is_middle_param(c)) {
+        return Err((StatusCode::BAD_REQUEST, "invalid command"));
+    }
+    if opt.arg.as_deref().is_some_and(|a| !is_middle_param(a)) {
+        return Err((StatusCode::BAD_REQUEST, "invalid arg"));
+    }
     dispatch_job(state, body, async move |state, lines| {
         let mut lines = lines.into_iter();
         let mut command = opt.command.unwrap_or_else(|| "PRIVMSG".to_string());
@@ -432,7 +1159,7 @@ async fn send(
                 next = active.next();
             }
 
-            let (hash, sub) = {
+            let (hash, sub, sender, line) = {
                 let clients = state.clients.read().await;
                 let Some(client) = &clients[slot] else {
                     state.active.write().await.remove(&slot);
@@ -453,9 +1180,9 @@ async fn send(
 
                 let sub = next.and_then(|n| clients[n].as_ref().map(|c| c.hash_feed.subscribe()));
 
-                _ = client.sender.try_send(line.format());
-                (hash, sub)
+                (hash, sub, client.sender.clone(), line.format())
             };
+            _ = send_tracked(&sender, line).await;
 
             if let Some(mut sub) = sub {
                 _ = tokio::time::timeout(std::time::Duration::from_secs(1), async {
@@ -464,12 +1191,83 @@ async fn send(
                 .await;
             }
 
-            state.job_sent.fetch_add(1, Ordering::SeqCst);
+            state.job_step();
         }
     })
     .await
 }
 
+/// stream the progress of jobs, with an event for each line sent and
+/// another when the job is over. the stream ends when shutting down
+async fn job_events(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut progress = state.job_progress.subscribe();
+    let mut shutting_down = state.shutting_down.subscribe();
+    let (tx, rx) = mpsc::channel::<Result<Event, Infallible>>(1);
+    tokio::spawn(async move {
+        loop {
+            let current = *progress.borrow_and_update();
+            let name = match current.state {
+                JobState::Running => "progress",
+                JobState::Finished => "finished",
+                JobState::Cancelled => "cancelled",
+            };
+            let Ok(event) = Event::default().event(name).json_data(current) else {
+                return;
+            };
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+            tokio::select! {
+                changed = progress.changed() => if changed.is_err() {
+                    return;
+                },
+                _ = shutting_down.wait_for(|s| *s) => return,
+            }
+        }
+    });
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+#[tokio::test]
+async fn events_end_on_shutdown() {
+    use tokio::io::AsyncReadExt;
+
+    let state = Arc::new(AppState::new(RootCertStore::empty(), None, None));
+    let app = Router::new()
+        .route("/job/events", get(job_events))
+        .with_state(state.clone());
+    let listen = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listen.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(
+        axum::serve(listen, app.into_make_service())
+            .with_graceful_shutdown(async move {
+                _ = stopped.await;
+                state.shutting_down.send_replace(true);
+            })
+            .into_future(),
+    );
+
+    let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+    conn.write_all(b"GET /job/events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut seen = Vec::new();
+    while !seen.windows(15).any(|w| w == b"event: finished") {
+        let mut buf = [0; 512];
+        let n = conn.read(&mut buf).await.unwrap();
+        assert_ne!(n, 0, "stream ended before the first event");
+        seen.extend_from_slice(&buf[..n]);
+    }
+
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not shut down with a subscriber connected")
+        .unwrap()
+        .unwrap();
+}
+
 async fn cancel(State(state): State<Arc<AppState>>) {
     state.job.read().await.abort();
 }
@@ -494,6 +1292,107 @@ async fn deactivate_all(State(state): State<Arc<AppState>>) {
     state.active.write().await.clear();
 }
 
+#[derive(Debug, Deserialize)]
+struct DisconnectOpt {
+    message: Option<String>,
+}
+
+async fn disconnect(
+    State(state): State<Arc<AppState>>,
+    Path(slot): Path<usize>,
+    Query(opt): Query<DisconnectOpt>,
+) -> StatusCode {
+    let Some(line) = quit_line(opt.message) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let Some(sender) = state
+        .clients
+        .read()
+        .await
+        .get(slot)
+        .and_then(|c| c.as_ref())
+        .map(|c| c.sender.clone())
+    else {
+        return StatusCode::NOT_FOUND;
+    };
+    // client_loop stops after sending a QUIT, which frees up the slot
+    if sender.send(line.into()).await.is_err() {
+        return StatusCode::NOT_FOUND;
+    }
+    StatusCode::OK
+}
+
+/// a QUIT line with an optional message, unless the message would
+/// sneak in more lines
+fn quit_line(message: Option<String>) -> Option<Vec<u8>> {
+    if message.as_deref().is_some_and(|m| !is_trailing_param(m)) {
+        return None;
+    }
+    let line = irctokens::Line {
+        tags: None,
+        source: None,
+        command: "QUIT".to_string(),
+        arguments: message.into_iter().map(String::into_bytes).collect(),
+    };
+    Some(line.format())
+}
+
+#[test]
+fn quit_message() {
+    assert_eq!(quit_line(None).as_deref(), Some(&b"QUIT"[..]));
+    assert_eq!(
+        quit_line(Some("bye now".to_string())).as_deref(),
+        Some(&b"QUIT :bye now"[..])
+    );
+    assert_eq!(quit_line(Some("bye\r\nPRIVMSG #a :hi".to_string())), None);
+    assert_eq!(quit_line(Some("bye\0".to_string())), None);
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesOpt {
+    #[serde(default)]
+    since: u64,
+    /// wait for a new message if there are none yet
+    #[serde(default)]
+    wait: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct MessagesReply {
+    /// pass this as since to only get newer messages
+    next: u64,
+    messages: Vec<Message>,
+}
+
+async fn get_messages(
+    State(state): State<Arc<AppState>>,
+    Path(slot): Path<usize>,
+    Query(opt): Query<MessagesOpt>,
+) -> Result<Json<MessagesReply>, StatusCode> {
+    let Some(log) = state
+        .clients
+        .read()
+        .await
+        .get(slot)
+        .and_then(|c| c.as_ref())
+        .map(|c| c.messages.clone())
+    else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let notified = log.new_message.notified();
+    tokio::pin!(notified);
+    notified.as_mut().enable();
+
+    let (mut next, mut messages) = log.since(opt.since).await;
+    if messages.is_empty() && opt.wait {
+        _ = tokio::time::timeout(std::time::Duration::from_secs(30), notified).await;
+        (next, messages) = log.since(opt.since).await;
+    }
+
+    Ok(Json(MessagesReply { next, messages }))
+}
+
 async fn get_raw(
     State(state): State<Arc<AppState>>,
     Path(slot): Path<usize>,
@@ -514,19 +1413,226 @@ async fn get_raw(
     Ok(body)
 }
 
+/// compare without bailing at the first differing byte, so the token
+/// cannot be guessed from response times
+fn token_matches(given: &[u8], token: &[u8]) -> bool {
+    given.len() == token.len() && given.iter().zip(token).fold(0, |a, (g, t)| a | (g ^ t)) == 0
+}
+
+/// the token from an `Authorization: Bearer` header
+fn bearer(headers: &HeaderMap) -> Option<&[u8]> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.as_bytes().strip_prefix(b"Bearer "))
+}
+
+async fn require_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(token) = &state.token else {
+        return Ok(next.run(request).await);
+    };
+    let given = bearer(request.headers()).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !token_matches(given, token.as_bytes()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(request).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct WsOpt {
+    /// browsers cannot set headers on websockets, so the token can be
+    /// given here instead
+    token: Option<String>,
+}
+
+/// the largest message a websocket client may send
+const WS_MAX_MESSAGE: usize = 64 * 1024;
+
+async fn ws_slot(
+    State(state): State<Arc<AppState>>,
+    Path(slot): Path<usize>,
+    Query(opt): Query<WsOpt>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    if let Some(token) = &state.token {
+        let given = bearer(&headers)
+            .or_else(|| opt.token.as_deref().map(str::as_bytes))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        if !token_matches(given, token.as_bytes()) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+    let Some((feed, sender)) = state
+        .clients
+        .read()
+        .await
+        .get(slot)
+        .and_then(|c| c.as_ref())
+        .map(|c| (c.raw_feed.subscribe(), c.sender.clone()))
+    else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    Ok(upgrade
+        .max_message_size(WS_MAX_MESSAGE)
+        .on_upgrade(move |socket| ws_loop(socket, feed, sender)))
+}
+
+#[tokio::test]
+async fn ws_token_in_query() {
+    use tokio::io::AsyncReadExt;
+
+    let state = Arc::new(AppState::new(
+        RootCertStore::empty(),
+        Some("meow".to_string()),
+        None,
+    ));
+    let app = Router::new()
+        .route("/ws/{slot}", get(ws_slot))
+        .with_state(state);
+    let listen = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listen.local_addr().unwrap();
+    tokio::spawn(axum::serve(listen, app.into_make_service()).into_future());
+
+    for (query, expected) in [
+        ("", "401"),
+        ("?token=woof", "401"),
+        // the token is right, there just is no client in that slot
+        ("?token=meow", "404"),
+    ] {
+        let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /ws/0{query} HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\n\
+             Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        );
+        conn.write_all(request.as_bytes()).await.unwrap();
+        let mut buf = [0; 12];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf[9..], expected.as_bytes(), "{query}");
+    }
+}
+
+/// forward lines from a client to the websocket and back, until
+/// either side goes away
+async fn ws_loop(
+    mut socket: WebSocket,
+    mut feed: broadcast::Receiver<Bytes>,
+    sender: mpsc::Sender<Outgoing>,
+) {
+    loop {
+        tokio::select! {
+            line = feed.recv() => match line {
+                Ok(line) => {
+                    let text = String::from_utf8_lossy(line.trim_ascii_end()).into_owned();
+                    if socket.send(WsMessage::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                // the slot is gone
+                Err(broadcast::error::RecvError::Closed) => {
+                    _ = socket.send(WsMessage::Close(None)).await;
+                    break;
+                }
+            },
+            message = socket.recv() => match message {
+                Some(Ok(WsMessage::Text(text))) => {
+                    let lines = text.as_str().split(['\r', '\n']).filter(|l| !l.is_empty());
+                    for line in lines {
+                        // the client is gone, so hang up as if the slot was
+                        if sender.send(line.as_bytes().to_vec().into()).await.is_err() {
+                            _ = socket.send(WsMessage::Close(None)).await;
+                            return;
+                        }
+                    }
+                }
+                // pings get answered by axum
+                Some(Ok(WsMessage::Binary(_) | WsMessage::Ping(_) | WsMessage::Pong(_))) => {}
+                Some(Ok(WsMessage::Close(_)) | Err(_)) | None => break,
+            },
+        }
+    }
+}
+
 async fn dashboard() -> Html<&'static str> {
     Html(include_str!("dashboard.html"))
 }
 
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("could not install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        () = terminate => (),
+    }
+}
+
+/// wait for a signal, then give the current job some time to finish
+/// before telling every client to quit
+async fn shutdown(state: Arc<AppState>, grace_period: std::time::Duration) {
+    wait_for_signal().await;
+    println!("shutting down");
+    state.shutting_down.send_replace(true);
+
+    _ = tokio::time::timeout(grace_period, async {
+        while !state.job.read().await.is_finished() {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    state.job.read().await.abort();
+
+    let senders: Vec<_> = state
+        .clients
+        .read()
+        .await
+        .iter()
+        .flatten()
+        .map(|c| c.sender.clone())
+        .collect();
+    for sender in senders {
+        _ = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            sender.send(b"QUIT".to_vec().into()),
+        )
+        .await;
+    }
+
+    // client_loop stops after sending a QUIT, ending any raw feeds
+    _ = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while state.clients.read().await.iter().any(Option::is_some) {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+}
+
 #[tokio::main]
 async fn main() {
-    let addr: SocketAddr = std::env::args()
-        .nth(1)
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(SocketAddr::new(
-            std::net::IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
-            8667,
-        ));
+    let opt: Opt = from_env();
+    if opt
+        .rate
+        .is_some_and(|rate| !(rate.is_finite() && rate > 0.0))
+    {
+        eprintln!("--rate must be a positive number");
+        std::process::exit(1);
+    }
+    let addr = opt.listen.unwrap_or(SocketAddr::new(
+        std::net::IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+        8667,
+    ));
 
     let mut ca_certs = RootCertStore::empty();
     ca_certs.add_parsable_certificates(
@@ -534,34 +1640,42 @@ async fn main() {
             .unwrap()
             .flatten(),
     );
-    let fake_job = tokio::spawn(async {}).abort_handle();
-
-    let state = Arc::new(AppState {
-        clients: RwLock::new(vec![]),
-        active: RwLock::new(BTreeSet::new()),
-        autojoin: RwLock::new(None),
-        job: RwLock::new(fake_job),
-        job_sent: AtomicUsize::new(0),
-        job_total: AtomicUsize::new(0),
-        ca_certs: Arc::new(ca_certs),
-    });
+
+    let state = Arc::new(AppState::new(
+        ca_certs,
+        opt.token.or_else(|| std::env::var("GEKKER_TOKEN").ok()),
+        opt.rate.map(|rate| (rate, opt.burst)),
+    ));
     let app = Router::new()
-        .route("/status", get(status))
         .route("/autojoin", post(set_autojoin))
         .route("/connect", post(connect))
         .route("/raw/all", post(raw_all))
         .route("/raw/active", post(raw_active))
         .route("/raw/{slot}", post(raw_slot))
         .route("/raw/{slot}", get(get_raw))
+        .route("/messages/{slot}", get(get_messages))
         .route("/send", post(send))
         .route("/cancel", post(cancel))
         .route("/activate/{slot}", post(activate))
         .route("/deactivate/all", post(deactivate_all))
         .route("/deactivate/{slot}", post(deactivate_slot))
+        .route("/disconnect/{slot}", post(disconnect))
+        .route("/metrics", get(metrics))
+        .route("/job/events", get(job_events))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        // checks the token itself, since it may also come from the query
+        .route("/ws/{slot}", get(ws_slot))
+        .route("/status", get(status))
         .route("/", get(dashboard))
-        .with_state(state);
+        .with_state(state.clone());
 
     let listen = TcpListener::bind(addr).await.unwrap();
     println!("listening on {}", listen.local_addr().unwrap());
-    axum::serve(listen, app.into_make_service()).await.unwrap();
+    axum::serve(listen, app.into_make_service())
+        .with_graceful_shutdown(shutdown(
+            state,
+            std::time::Duration::from_secs(opt.grace_period),
+        ))
+        .await
+        .unwrap();
 }
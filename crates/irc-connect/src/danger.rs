@@ -2,12 +2,14 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::sync::Arc;
+use crate::UnverifiedCert;
+use std::{fmt, sync::Arc};
 use tokio_rustls::rustls::{
     self,
-    client::danger::HandshakeSignatureValid,
+    client::{danger::HandshakeSignatureValid, verify_server_name},
     crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
     pki_types::{CertificateDer, ServerName, UnixTime},
+    server::ParsedCertificate,
     DigitallySignedStruct,
 };
 
@@ -68,3 +70,74 @@ impl rustls::client::danger::ServerCertVerifier for PhonyVerify {
         self.0.signature_verification_algorithms.supported_schemes()
     }
 }
+
+/// called with what [`LoggingVerify`] saw
+#[derive(Clone)]
+pub struct CertCallback(pub Arc<dyn Fn(&UnverifiedCert) + Send + Sync>);
+
+impl fmt::Debug for CertCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CertCallback")
+    }
+}
+
+/// like [`PhonyVerify`], but tells a callback about every certificate
+/// and whether it would have matched the server name
+#[derive(Debug)]
+pub struct LoggingVerify {
+    phony: PhonyVerify,
+    callback: CertCallback,
+}
+
+impl LoggingVerify {
+    pub fn new(provider: Arc<CryptoProvider>, callback: CertCallback) -> Arc<Self> {
+        Arc::new(Self {
+            phony: PhonyVerify(provider),
+            callback,
+        })
+    }
+}
+
+impl rustls::client::danger::ServerCertVerifier for LoggingVerify {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp: &[u8],
+        now: UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        let name_matched = ParsedCertificate::try_from(end_entity)
+            .and_then(|cert| verify_server_name(&cert, server_name))
+            .is_ok();
+        (self.callback.0)(&UnverifiedCert {
+            server_name: server_name.to_owned(),
+            name_matched,
+            end_entity: end_entity.clone().into_owned(),
+        });
+        self.phony
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.phony.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.phony.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.phony.supported_verify_schemes()
+    }
+}
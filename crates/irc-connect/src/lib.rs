@@ -14,14 +14,20 @@ use std::{
     task::{Context, Poll},
 };
 use tokio::{
-    io::{AsyncRead, AsyncWrite, ReadBuf},
-    net::{TcpStream, UnixStream},
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf, ReadHalf,
+        WriteHalf,
+    },
+    net::{tcp, unix, TcpStream, UnixStream},
 };
 use tokio_rustls::{
     client::TlsStream,
     rustls::{
         client::WebPkiServerVerifier,
-        pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+        pki_types::{
+            pem::{self, PemObject},
+            CertificateDer, PrivateKeyDer, ServerName,
+        },
         ClientConfig, RootCertStore,
     },
     TlsConnector,
@@ -51,16 +57,33 @@ pub enum Error {
     ClientCertNoTls,
     /// failed to connect
     Connect(std::io::Error),
+    /// timed out while connecting
+    Timeout,
+    /// could not resolve the domain
+    DnsResolution(String),
     /// could not sock
     Socks(tokio_socks::Error),
     /// could not rustls
     Rustls(tokio_rustls::rustls::Error),
+    /// could not parse a pem certificate or key
+    Pem(pem::Error),
     /// socks cannot connect to unix sockets
     SocksToUnsupported,
+    /// the socks4 proxy rejected or failed a socks4a request for a
+    /// domain. socks4 gives no reason, it may not support socks4a, or
+    /// it may not have been able or allowed to reach the target
+    Socks4aRejected(String),
     /// invalid target address
     InvalidTarget(tokio_socks::Error),
     /// no tls servername provided and failed to guess it
     NoServerName,
+    /// could not connect to any of the targets
+    AllFailed {
+        /// how many targets were tried
+        tried: usize,
+        /// the error from the last target
+        last: Box<Self>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -68,11 +91,23 @@ impl fmt::Display for Error {
         match self {
             Self::ClientCertNoTls => write!(f, "you specified a client cert without using tls"),
             Self::Connect(e) => write!(f, "failed to connect: {e}"),
+            Self::Timeout => write!(f, "timed out while connecting"),
+            Self::DnsResolution(e) => write!(f, "could not resolve the domain: {e}"),
             Self::Socks(e) => write!(f, "could not sock: {e}"),
             Self::Rustls(e) => write!(f, "could not rustls: {e}"),
+            Self::Pem(e) => write!(f, "could not parse pem: {e}"),
             Self::SocksToUnsupported => write!(f, "socks cannot connect to unix sockets"),
+            Self::Socks4aRejected(domain) => {
+                write!(f, "socks4 proxy rejected or failed connecting to {domain}")
+            }
             Self::InvalidTarget(e) => write!(f, "invalid target address: {e}"),
             Self::NoServerName => write!(f, "no tls servername provided and failed to guess it"),
+            Self::AllFailed { tried, last } => {
+                write!(
+                    f,
+                    "could not connect to any of {tried} targets, last: {last}"
+                )
+            }
         }
     }
 }
@@ -85,6 +120,36 @@ impl From<std::io::Error> for Error {
     }
 }
 
+/// like the [`From`] impl, but tells timeouts apart
+fn connect_error(e: std::io::Error) -> Error {
+    if e.kind() == std::io::ErrorKind::TimedOut {
+        Error::Timeout
+    } else {
+        Error::Connect(e)
+    }
+}
+
+async fn connect_tcp(addr: TargetAddr<'_>) -> Result<TcpStream, Error> {
+    let addrs: Vec<SocketAddr> = match addr {
+        TargetAddr::Ip(addr) => vec![addr],
+        TargetAddr::Domain(domain, port) => tokio::net::lookup_host((domain.as_ref(), port))
+            .await
+            .map_err(|e| Error::DnsResolution(format!("{domain}: {e}")))?
+            .collect(),
+    };
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.map_or_else(
+        || Error::DnsResolution("no addresses found".to_string()),
+        connect_error,
+    ))
+}
+
 impl From<tokio_socks::Error> for Error {
     fn from(value: tokio_socks::Error) -> Self {
         Self::Socks(value)
@@ -97,12 +162,28 @@ impl From<tokio_rustls::rustls::Error> for Error {
     }
 }
 
+impl From<pem::Error> for Error {
+    fn from(value: pem::Error) -> Self {
+        Self::Pem(value)
+    }
+}
+
+/// parse every certificate in some pem, there must be at least one
+fn pem_certs(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_slice_iter(pem).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(pem::Error::NoItemsFound.into());
+    }
+    Ok(certs)
+}
+
 pin_project! {
     /// an open connection
     #[derive(Debug)]
     pub struct Connection {
         #[pin]
         inner: MaybeTls,
+        tag: Option<String>,
     }
 }
 
@@ -121,6 +202,9 @@ impl Connection {
     }
     /// start building a new stream based on a unix socket
     ///
+    /// socks proxying is not supported for unix sockets, connecting
+    /// with it enabled fails with [`Error::SocksToUnsupported`]
+    ///
     /// ```no_run
     /// use std::path::Path;
     /// use irc_connect::Connection;
@@ -132,6 +216,310 @@ impl Connection {
     pub fn new_unix(path: &Path) -> ConnectionBuilder<'_> {
         ConnectionBuilder::new(BaseParams::Unix(path))
     }
+
+    /// start building a new stream on top of an already connected tcp
+    /// stream, like one from an accept loop
+    ///
+    /// `target` is where the stream ends up, used for guessing the tls
+    /// servername. with socks, `stream` should be connected to the proxy
+    /// instead, and `target` is what to ask it for. the proxy address
+    /// given to the socks methods is unused then
+    ///
+    /// ```
+    /// use irc_connect::Connection;
+    /// use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    /// let addr = listener.local_addr().unwrap();
+    /// let (ours, theirs) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    /// let mut conn = Connection::from_tcp_stream(ours.unwrap(), addr)
+    ///     .connect()
+    ///     .await
+    ///     .unwrap();
+    /// conn.write_all(b"PING :meow\r\n").await.unwrap();
+    /// let mut buf = [0; 12];
+    /// theirs.unwrap().0.read_exact(&mut buf).await.unwrap();
+    /// assert_eq!(&buf, b"PING :meow\r\n");
+    /// # }
+    /// ```
+    pub fn from_tcp_stream<'a>(
+        stream: TcpStream,
+        target: impl IntoTargetAddr<'a>,
+    ) -> ConnectionBuilder<'a> {
+        ConnectionBuilder::new(BaseParams::TcpStream(stream, target.into_target_addr()))
+    }
+
+    /// start building a new stream on top of an already connected unix
+    /// socket, like one passed down from a parent process
+    ///
+    /// there is nothing to guess a tls servername from, so give one when
+    /// enabling tls. socks is not supported, just like with
+    /// [`Self::new_unix`]
+    ///
+    /// ```no_run
+    /// use irc_connect::Connection;
+    /// use tokio::net::UnixStream;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let stream = UnixStream::connect("./my-unix-socket").await.unwrap();
+    /// let conn = Connection::from_unix_stream(stream).connect().await.unwrap();
+    /// # }
+    /// ```
+    pub fn from_unix_stream<'a>(stream: UnixStream) -> ConnectionBuilder<'a> {
+        ConnectionBuilder::new(BaseParams::UnixStream(stream))
+    }
+
+    /// the tag given with [`ConnectionBuilder::tag`]
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// the local address of the underlying tcp connection, useful for
+    /// answering ident queries
+    ///
+    /// returns `None` for unix sockets
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self.base() {
+            BaseStream::Tcp { inner } => inner.local_addr().ok(),
+            BaseStream::Unix { .. } => None,
+        }
+    }
+
+    /// the remote address of the underlying tcp connection
+    ///
+    /// this is the proxy when using socks, and `None` for unix sockets
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self.base() {
+            BaseStream::Tcp { inner } => inner.peer_addr().ok(),
+            BaseStream::Unix { .. } => None,
+        }
+    }
+
+    fn base(&self) -> &BaseStream {
+        let socks = match &self.inner {
+            MaybeTls::Plain { inner } => inner,
+            MaybeTls::Tls { inner } => inner.get_ref().0,
+        };
+        match socks {
+            MaybeSocks::Clear { inner } => inner,
+            MaybeSocks::Socks4 { inner } => inner,
+            MaybeSocks::Socks5 { inner } => inner,
+        }
+    }
+
+    /// wrap in a reader and writer of whole irc lines, so you do not
+    /// have to split on crlf yourself
+    ///
+    /// ```
+    /// use irc_connect::Connection;
+    /// use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{TcpListener, TcpStream}};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    /// # let addr = listener.local_addr().unwrap();
+    /// # let (ours, theirs) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    /// # let mut theirs = BufReader::new(theirs.unwrap().0);
+    /// let conn = Connection::from_tcp_stream(ours.unwrap(), addr).connect().await.unwrap();
+    /// let mut lines = conn.into_lines();
+    /// lines.send_line(b"NICK meow").await.unwrap();
+    /// # let mut buf = vec![];
+    /// # theirs.read_until(b'\n', &mut buf).await.unwrap();
+    /// # assert_eq!(buf, b"NICK meow\r\n");
+    /// # theirs.write_all(b"PING :one\r\nPING :two\n").await.unwrap();
+    /// # drop(theirs);
+    /// assert_eq!(lines.read_line().await.unwrap().unwrap(), b"PING :one");
+    /// assert_eq!(lines.read_line().await.unwrap().unwrap(), b"PING :two");
+    /// // until the server hangs up
+    /// assert_eq!(lines.read_line().await.unwrap(), None);
+    /// # }
+    /// ```
+    pub fn into_lines(self) -> IrcLines {
+        IrcLines {
+            inner: BufReader::new(self),
+        }
+    }
+
+    /// split into halves that can be moved into separate tasks
+    ///
+    /// plain connections get split for real, but tls ones have to share
+    /// the stream with [`tokio::io::split`]
+    ///
+    /// ```no_run
+    /// use irc_connect::Connection;
+    /// use tokio::io::AsyncWriteExt;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let stream = Connection::new_tcp("[::1]:6667").connect().await.unwrap();
+    /// let (read, mut write) = stream.into_split();
+    /// write.write_all(b"NICK meow\r\n").await.unwrap();
+    /// # }
+    /// ```
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let base = match self.inner {
+            MaybeTls::Tls { inner } => {
+                let (read, write) = tokio::io::split(inner);
+                return (
+                    OwnedReadHalf(ReadInner::Tls(read)),
+                    OwnedWriteHalf(WriteInner::Tls(write)),
+                );
+            }
+            MaybeTls::Plain { inner } => match inner {
+                MaybeSocks::Clear { inner } => inner,
+                // socks is done with once the handshake is over
+                MaybeSocks::Socks4 { inner } => inner.into_inner(),
+                MaybeSocks::Socks5 { inner } => inner.into_inner(),
+            },
+        };
+        match base {
+            BaseStream::Tcp { inner } => {
+                let (read, write) = inner.into_split();
+                (
+                    OwnedReadHalf(ReadInner::Tcp(read)),
+                    OwnedWriteHalf(WriteInner::Tcp(write)),
+                )
+            }
+            BaseStream::Unix { inner } => {
+                let (read, write) = inner.into_split();
+                (
+                    OwnedReadHalf(ReadInner::Unix(read)),
+                    OwnedWriteHalf(WriteInner::Unix(write)),
+                )
+            }
+        }
+    }
+}
+
+/// a [`Connection`] sending and receiving whole lines, from
+/// [`Connection::into_lines`]
+#[derive(Debug)]
+pub struct IrcLines {
+    inner: BufReader<Connection>,
+}
+
+impl IrcLines {
+    /// read the next line, without the line ending
+    ///
+    /// lines ending with a bare `\n` are accepted too. gives `None`
+    /// once the connection is closed
+    ///
+    /// # Errors
+    /// will return [`std::io::Error`] if reading fails
+    pub async fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut line = vec![];
+        if self.inner.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    /// send a line, adding the crlf and flushing
+    ///
+    /// # Errors
+    /// will return [`std::io::Error`] if writing fails, or with
+    /// [`std::io::ErrorKind::InvalidInput`] if `line` has a `\r` or `\n`
+    /// in it, since that would send more than one line
+    pub async fn send_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if line.iter().any(|b| b"\r\n".contains(b)) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "line contains a line break",
+            ));
+        }
+        let conn = self.inner.get_mut();
+        conn.write_all(&[line, b"\r\n"].concat()).await?;
+        conn.flush().await
+    }
+
+    /// get the connection underneath
+    pub fn get_ref(&self) -> &Connection {
+        self.inner.get_ref()
+    }
+
+    /// get the connection underneath, for writing raw bytes
+    ///
+    /// reading from it directly will skip over anything already
+    /// buffered for [`Self::read_line`]
+    pub fn get_mut(&mut self) -> &mut Connection {
+        self.inner.get_mut()
+    }
+}
+
+/// the reading half of a [`Connection`], from [`Connection::into_split`]
+#[derive(Debug)]
+pub struct OwnedReadHalf(ReadInner);
+
+#[derive(Debug)]
+enum ReadInner {
+    Tcp(tcp::OwnedReadHalf),
+    Unix(unix::OwnedReadHalf),
+    Tls(ReadHalf<TlsStream<MaybeSocks>>),
+}
+
+/// the writing half of a [`Connection`], from [`Connection::into_split`]
+#[derive(Debug)]
+pub struct OwnedWriteHalf(WriteInner);
+
+#[derive(Debug)]
+enum WriteInner {
+    Tcp(tcp::OwnedWriteHalf),
+    Unix(unix::OwnedWriteHalf),
+    Tls(WriteHalf<TlsStream<MaybeSocks>>),
+}
+
+impl AsyncRead for OwnedReadHalf {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match &mut self.get_mut().0 {
+            ReadInner::Tcp(inner) => Pin::new(inner).poll_read(cx, buf),
+            ReadInner::Unix(inner) => Pin::new(inner).poll_read(cx, buf),
+            ReadInner::Tls(inner) => Pin::new(inner).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        match &mut self.get_mut().0 {
+            WriteInner::Tcp(inner) => Pin::new(inner).poll_write(cx, buf),
+            WriteInner::Unix(inner) => Pin::new(inner).poll_write(cx, buf),
+            WriteInner::Tls(inner) => Pin::new(inner).poll_write(cx, buf),
+        }
+    }
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        match &mut self.get_mut().0 {
+            WriteInner::Tcp(inner) => Pin::new(inner).poll_flush(cx),
+            WriteInner::Unix(inner) => Pin::new(inner).poll_flush(cx),
+            WriteInner::Tls(inner) => Pin::new(inner).poll_flush(cx),
+        }
+    }
+    #[inline]
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        match &mut self.get_mut().0 {
+            WriteInner::Tcp(inner) => Pin::new(inner).poll_shutdown(cx),
+            WriteInner::Unix(inner) => Pin::new(inner).poll_shutdown(cx),
+            WriteInner::Tls(inner) => Pin::new(inner).poll_shutdown(cx),
+        }
+    }
 }
 
 impl AsyncRead for Connection {
@@ -283,18 +671,22 @@ trivial_impl!(BaseStream, (BaseStreamProj::Tcp, BaseStreamProj::Unix));
 #[must_use = "this does nothing unless you finish building"]
 pub struct ConnectionBuilder<'a> {
     base: BaseParams<'a>,
+    fallbacks: Vec<BaseParams<'a>>,
     socks: Option<SocksParams<'a>>,
     tls: Option<TlsParams>,
     client_cert: Option<ClientCert>,
+    tag: Option<String>,
 }
 
 impl<'a> ConnectionBuilder<'a> {
     fn new(base: BaseParams<'a>) -> Self {
         Self {
             base,
+            fallbacks: vec![],
             socks: None,
             tls: None,
             client_cert: None,
+            tag: None,
         }
     }
 
@@ -314,6 +706,12 @@ impl<'a> ConnectionBuilder<'a> {
 
     /// enable socks4 proxying
     ///
+    /// domain targets are passed to the proxy to resolve using socks4a,
+    /// if the proxy rejects or fails such a request connecting fails
+    /// with [`Error::Socks4aRejected`]. proxying only works for tcp, with
+    /// [`Connection::new_unix`] connecting fails with
+    /// [`Error::SocksToUnsupported`]
+    ///
     /// ```
     /// # use irc_connect::Connection;
     /// # #[tokio::main]
@@ -326,7 +724,8 @@ impl<'a> ConnectionBuilder<'a> {
         self.socks(SocksVersion::Socks4, proxy, None)
     }
 
-    /// enable socks4 proxying with a userid
+    /// enable socks4 proxying with a userid, otherwise the same as
+    /// [`Self::socks4`]
     ///
     /// ```
     /// # use irc_connect::Connection;
@@ -349,6 +748,10 @@ impl<'a> ConnectionBuilder<'a> {
 
     /// enable socks5 proxying
     ///
+    /// domain targets are passed to the proxy to resolve. proxying only
+    /// works for tcp, with [`Connection::new_unix`] connecting fails
+    /// with [`Error::SocksToUnsupported`]
+    ///
     /// ```
     /// # use irc_connect::Connection;
     /// # #[tokio::main]
@@ -361,7 +764,8 @@ impl<'a> ConnectionBuilder<'a> {
         self.socks(SocksVersion::Socks5, proxy, None)
     }
 
-    /// enable socks5 proxying with password authentication
+    /// enable socks5 proxying with password authentication, otherwise
+    /// the same as [`Self::socks5`]
     ///
     /// ```
     /// # use irc_connect::Connection;
@@ -408,6 +812,38 @@ impl<'a> ConnectionBuilder<'a> {
         self.tls(domain, TlsVerify::Insecure)
     }
 
+    /// enable tls without any verification, like
+    /// [`Self::tls_danger_insecure`], but call `callback` with every
+    /// certificate the server presents
+    ///
+    /// the connection still goes through when the certificate does not
+    /// match, this is only for figuring out why verifying would fail
+    ///
+    /// ```
+    /// use tokio_rustls::rustls::pki_types::ServerName;
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let builder = Connection::new_tcp("[::1]:6697");
+    /// let domain = ServerName::try_from("irc.example.com").unwrap();
+    /// let builder = builder.tls_danger_insecure_logging(Some(domain), |cert| {
+    ///     if !cert.name_matched {
+    ///         eprintln!("certificate is not valid for {:?}", cert.server_name);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn tls_danger_insecure_logging(
+        self,
+        domain: Option<ServerName<'static>>,
+        callback: impl Fn(&UnverifiedCert) + Send + Sync + 'static,
+    ) -> Self {
+        self.tls(
+            domain,
+            TlsVerify::InsecureLogging(danger::CertCallback(Arc::new(callback))),
+        )
+    }
+
     /// enable tls with root certificate verification
     ///
     /// can also be used to pin a self-signed cert as long as it has a `CA:FALSE` constraint
@@ -437,6 +873,35 @@ impl<'a> ConnectionBuilder<'a> {
         self.tls(domain, TlsVerify::CaStore(root.into()))
     }
 
+    /// enable tls with root certificates parsed from pem, otherwise the
+    /// same as [`Self::tls_with_root`]
+    ///
+    /// ```no_run
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let builder = Connection::new_tcp("[::1]:6667");
+    /// let ca = std::env::var("IRC_CA").unwrap();
+    /// let builder = builder.tls_with_root_pem(None, ca.as_bytes()).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// will return [`Error::Pem`] if the pem has no certificates or any
+    /// of them cannot be parsed, or [`Error::Rustls`] if one is not
+    /// usable as a root
+    pub fn tls_with_root_pem(
+        self,
+        domain: Option<ServerName<'static>>,
+        ca_pem: &[u8],
+    ) -> Result<Self, Error> {
+        let mut root = RootCertStore::empty();
+        for cert in pem_certs(ca_pem)? {
+            root.add(cert)?;
+        }
+        Ok(self.tls_with_root(domain, root))
+    }
+
     /// enable tls with a webpki verifier
     pub fn tls_with_webpki(
         self,
@@ -478,6 +943,71 @@ impl<'a> ConnectionBuilder<'a> {
         self
     }
 
+    /// use a tls client certificate parsed from pem, otherwise the
+    /// same as [`Self::client_cert`]
+    ///
+    /// `cert_pem` may hold a whole chain, and the first private key
+    /// found in `key_pem` is used
+    ///
+    /// ```no_run
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let builder = Connection::new_tcp("[::1]:6667").tls_danger_insecure(None);
+    /// let cert = std::env::var("IRC_CERT").unwrap();
+    /// let key = std::env::var("IRC_KEY").unwrap();
+    /// let builder = builder
+    ///     .client_cert_pem(cert.as_bytes(), key.as_bytes())
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// will return [`Error::Pem`] if either cannot be parsed
+    pub fn client_cert_pem(self, cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, Error> {
+        let cert_chain = pem_certs(cert_pem)?;
+        let key_der = PrivateKeyDer::from_pem_slice(key_pem)?;
+        Ok(self.client_cert(cert_chain, key_der))
+    }
+
+    /// more tcp targets to try in order, if connecting to the first one
+    /// fails
+    ///
+    /// socks and tls settings apply to all of them
+    ///
+    /// ```
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let builder = Connection::new_tcp("irc1.example.com:6697")
+    ///     .fallbacks(["irc2.example.com:6697", "irc3.example.com:6697"]);
+    /// # }
+    /// ```
+    pub fn fallbacks(mut self, targets: impl IntoIterator<Item = impl IntoTargetAddr<'a>>) -> Self {
+        self.fallbacks.extend(
+            targets
+                .into_iter()
+                .map(|t| BaseParams::Tcp(t.into_target_addr())),
+        );
+        self
+    }
+
+    /// attach a label to the connection, such as which configuration it
+    /// came from, that can be read back with [`Connection::tag`]
+    ///
+    /// ```
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let builder = Connection::new_tcp("[::1]:6667");
+    /// let builder = builder.tag("libera");
+    /// # }
+    /// ```
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
     /// finish building and open the connection
     ///
     /// ```no_run
@@ -493,35 +1023,198 @@ impl<'a> ConnectionBuilder<'a> {
     /// will return [`Error`] if an invalid combination of options has been
     /// given to the builder, or if it is unable to connect
     pub async fn connect(self) -> Result<Connection, Error> {
-        let tls = if let Some(mut params) = self.tls {
-            params.domain = params.domain.or_else(|| match &self.base {
-                BaseParams::Tcp(Ok(TargetAddr::Ip(addr))) => Some(ServerName::from(addr.ip())),
-                BaseParams::Tcp(Ok(TargetAddr::Domain(d, _))) => {
+        // fail before trying any fallbacks, which would otherwise hide
+        // this behind whichever error they ended with
+        if self.socks.is_some()
+            && matches!(self.base, BaseParams::Unix(_) | BaseParams::UnixStream(_))
+        {
+            return Err(Error::SocksToUnsupported);
+        }
+        let tried = self.fallbacks.len() + 1;
+        let connector = Connector::new(self.socks, self.tls, self.client_cert, self.tag)?;
+        let mut last = match connector.connect_base(self.base).await {
+            Ok(conn) => return Ok(conn),
+            Err(e) if tried == 1 => return Err(e),
+            Err(e) => e,
+        };
+        for base in self.fallbacks {
+            match connector.connect_base(base).await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => last = e,
+            }
+        }
+        Err(Error::AllFailed {
+            tried,
+            last: Box::new(last),
+        })
+    }
+
+    /// finish building into a [`Connector`], which can open many
+    /// connections with these settings while only setting up tls once
+    ///
+    /// the target the builder was started with and any fallbacks are
+    /// not kept, targets are given to [`Connector::connect`] instead
+    ///
+    /// ```no_run
+    /// # use irc_connect::Connection;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let connector = Connection::new_tcp("irc.example.com:6697")
+    ///     .tls_danger_insecure(None)
+    ///     .into_connector()
+    ///     .unwrap();
+    /// let first = connector.connect("irc.example.com:6697").await.unwrap();
+    /// let second = connector.connect("irc.example.com:6697").await.unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// will return [`Error`] if an invalid combination of options has been
+    /// given to the builder
+    pub fn into_connector(self) -> Result<Connector<'a>, Error> {
+        Connector::new(self.socks, self.tls, self.client_cert, self.tag)
+    }
+}
+
+/// a certificate seen by
+/// [`ConnectionBuilder::tls_danger_insecure_logging`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct UnverifiedCert {
+    /// the server name that was sent with sni
+    pub server_name: ServerName<'static>,
+    /// whether the certificate is valid for `server_name`, ignoring
+    /// who signed it and when it expires
+    pub name_matched: bool,
+    /// the certificate the server presented
+    pub end_entity: CertificateDer<'static>,
+}
+
+/// prebuilt connection settings for opening many connections, from
+/// [`ConnectionBuilder::into_connector`]
+#[derive(Debug, Clone)]
+pub struct Connector<'a> {
+    socks: Option<SocksParams<'a>>,
+    tls: Option<(Option<ServerName<'static>>, Arc<ClientConfig>)>,
+    tag: Option<String>,
+}
+
+impl<'a> Connector<'a> {
+    fn new(
+        socks: Option<SocksParams<'a>>,
+        tls: Option<TlsParams>,
+        client_cert: Option<ClientCert>,
+        tag: Option<String>,
+    ) -> Result<Self, Error> {
+        let tls = if let Some(params) = tls {
+            let config = ClientConfig::builder();
+            let config =
+                match params.verification {
+                    TlsVerify::Insecure => {
+                        let provider = config.crypto_provider().clone();
+                        config
+                            .dangerous()
+                            .with_custom_certificate_verifier(danger::PhonyVerify::new(provider))
+                    }
+                    TlsVerify::InsecureLogging(callback) => {
+                        let provider = config.crypto_provider().clone();
+                        config.dangerous().with_custom_certificate_verifier(
+                            danger::LoggingVerify::new(provider, callback),
+                        )
+                    }
+                    TlsVerify::CaStore(root) => config.with_root_certificates(root),
+                    TlsVerify::WebPki(webpki) => config.with_webpki_verifier(webpki),
+                };
+            let config = if let Some(ClientCert {
+                cert_chain,
+                key_der,
+            }) = client_cert
+            {
+                config.with_client_auth_cert(cert_chain, key_der)?
+            } else {
+                config.with_no_client_auth()
+            };
+            Some((params.domain, Arc::new(config)))
+        } else {
+            if client_cert.is_some() {
+                return Err(Error::ClientCertNoTls);
+            }
+            None
+        };
+        Ok(Self { socks, tls, tag })
+    }
+
+    /// open a tcp connection to a target
+    ///
+    /// # Errors
+    /// will return [`Error`] if it is unable to connect
+    pub async fn connect(&self, target: impl IntoTargetAddr<'a>) -> Result<Connection, Error> {
+        self.connect_base(BaseParams::Tcp(target.into_target_addr()))
+            .await
+    }
+
+    /// open a unix socket connection
+    ///
+    /// # Errors
+    /// will return [`Error`] if it is unable to connect, or if socks is
+    /// enabled
+    pub async fn connect_unix(&self, path: &'a Path) -> Result<Connection, Error> {
+        self.connect_base(BaseParams::Unix(path)).await
+    }
+
+    async fn connect_base(&self, base: BaseParams<'a>) -> Result<Connection, Error> {
+        let tls = self.tls.as_ref().map(|(domain, config)| {
+            let domain = domain.clone().or_else(|| match &base {
+                BaseParams::Tcp(Ok(TargetAddr::Ip(addr)))
+                | BaseParams::TcpStream(_, Ok(TargetAddr::Ip(addr))) => {
+                    Some(ServerName::from(addr.ip()))
+                }
+                BaseParams::Tcp(Ok(TargetAddr::Domain(d, _)))
+                | BaseParams::TcpStream(_, Ok(TargetAddr::Domain(d, _))) => {
                     ServerName::try_from(d.as_ref()).map(|s| s.to_owned()).ok()
                 }
                 _ => None,
             });
-            Some(params)
-        } else {
-            None
-        };
-        let stream = if let Some(params) = self.socks {
-            let BaseParams::Tcp(target) = self.base else {
-                return Err(Error::SocksToUnsupported);
-            };
-            let target = target.map_err(Error::InvalidTarget)?;
-            let stream = BaseStream::Tcp {
-                inner: TcpStream::connect(params.proxy).await?,
+            (domain, config)
+        });
+        let stream = if let Some(params) = &self.socks {
+            let (stream, target) = match base {
+                BaseParams::Tcp(target) => {
+                    let target = target.map_err(Error::InvalidTarget)?;
+                    let stream = TcpStream::connect(params.proxy)
+                        .await
+                        .map_err(connect_error)?;
+                    (stream, target)
+                }
+                BaseParams::TcpStream(stream, target) => {
+                    (stream, target.map_err(Error::InvalidTarget)?)
+                }
+                BaseParams::Unix(_) | BaseParams::UnixStream(_) => {
+                    return Err(Error::SocksToUnsupported);
+                }
             };
+            let stream = BaseStream::Tcp { inner: stream };
             match params.version {
-                SocksVersion::Socks4 => MaybeSocks::Socks4 {
-                    inner: if let Some(SocksAuth { username, .. }) = params.auth {
-                        Socks4Stream::connect_with_userid_and_socket(stream, target, username)
-                            .await?
+                SocksVersion::Socks4 => {
+                    let domain = match &target {
+                        TargetAddr::Domain(domain, _) => Some(domain.to_string()),
+                        TargetAddr::Ip(_) => None,
+                    };
+                    let inner = if let Some(SocksAuth { username, .. }) = params.auth {
+                        Socks4Stream::connect_with_userid_and_socket(stream, target, username).await
                     } else {
-                        Socks4Stream::connect_with_socket(stream, target).await?
-                    },
-                },
+                        Socks4Stream::connect_with_socket(stream, target).await
+                    };
+                    // socks4 has no way to say why, so only note that it
+                    // was for a domain, since lacking socks4a is one reason
+                    let inner = inner.map_err(|e| match (e, domain) {
+                        (tokio_socks::Error::GeneralSocksServerFailure, Some(domain)) => {
+                            Error::Socks4aRejected(domain)
+                        }
+                        (e, _) => Error::Socks(e),
+                    })?;
+                    MaybeSocks::Socks4 { inner }
+                }
                 SocksVersion::Socks5 => MaybeSocks::Socks5 {
                     inner: if let Some(SocksAuth { username, password }) = params.auth {
                         Socks5Stream::connect_with_password_and_socket(
@@ -534,56 +1227,30 @@ impl<'a> ConnectionBuilder<'a> {
                 },
             }
         } else {
-            let stream = match self.base {
-                BaseParams::Tcp(addr) => {
-                    // FIXME: stick addr into connect directly, once tokio's ToSocketAddrs
-                    // stabilizes and TargetAddr implements it
-                    let inner = match addr.map_err(Error::InvalidTarget)? {
-                        TargetAddr::Ip(addr) => TcpStream::connect(addr).await?,
-                        TargetAddr::Domain(domain, port) => {
-                            TcpStream::connect((domain.as_ref(), port)).await?
-                        }
-                    };
-                    BaseStream::Tcp { inner }
-                }
+            let stream = match base {
+                BaseParams::Tcp(addr) => BaseStream::Tcp {
+                    inner: connect_tcp(addr.map_err(Error::InvalidTarget)?).await?,
+                },
                 BaseParams::Unix(path) => BaseStream::Unix {
-                    inner: UnixStream::connect(path).await?,
+                    inner: UnixStream::connect(path).await.map_err(connect_error)?,
                 },
+                BaseParams::TcpStream(inner, _) => BaseStream::Tcp { inner },
+                BaseParams::UnixStream(inner) => BaseStream::Unix { inner },
             };
             MaybeSocks::Clear { inner: stream }
         };
-        let stream = if let Some(params) = tls {
-            let config = ClientConfig::builder();
-            let config = match params.verification {
-                TlsVerify::Insecure => {
-                    let provider = config.crypto_provider().clone();
-                    config
-                        .dangerous()
-                        .with_custom_certificate_verifier(danger::PhonyVerify::new(provider))
-                }
-                TlsVerify::CaStore(root) => config.with_root_certificates(root),
-                TlsVerify::WebPki(webpki) => config.with_webpki_verifier(webpki),
-            };
-            let config = if let Some(ClientCert {
-                cert_chain,
-                key_der,
-            }) = self.client_cert
-            {
-                config.with_client_auth_cert(cert_chain, key_der)?
-            } else {
-                config.with_no_client_auth()
-            };
-            let connector = TlsConnector::from(Arc::new(config));
-            let domain = params.domain.ok_or(Error::NoServerName)?;
+        let stream = if let Some((domain, config)) = tls {
+            let connector = TlsConnector::from(config.clone());
+            let domain = domain.ok_or(Error::NoServerName)?;
             let inner = connector.connect(domain, stream).await?;
             MaybeTls::Tls { inner }
         } else {
-            if self.client_cert.is_some() {
-                return Err(Error::ClientCertNoTls);
-            }
             MaybeTls::Plain { inner: stream }
         };
-        Ok(Connection { inner: stream })
+        Ok(Connection {
+            inner: stream,
+            tag: self.tag.clone(),
+        })
     }
 }
 
@@ -591,8 +1258,12 @@ impl<'a> ConnectionBuilder<'a> {
 enum BaseParams<'a> {
     Tcp(tokio_socks::Result<TargetAddr<'a>>),
     Unix(&'a Path),
+    /// already connected, to the target or a socks proxy
+    TcpStream(TcpStream, tokio_socks::Result<TargetAddr<'a>>),
+    UnixStream(UnixStream),
 }
 
+#[derive(Clone)]
 struct SocksParams<'a> {
     version: SocksVersion,
     proxy: SocketAddr,
@@ -605,12 +1276,13 @@ impl fmt::Debug for SocksParams<'_> {
     }
 }
 
+#[derive(Clone)]
 struct SocksAuth<'a> {
     username: &'a str,
     password: &'a str,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum SocksVersion {
     Socks4,
     Socks5,
@@ -625,6 +1297,7 @@ struct TlsParams {
 #[derive(Debug)]
 enum TlsVerify {
     Insecure,
+    InsecureLogging(danger::CertCallback),
     CaStore(Arc<RootCertStore>),
     WebPki(Arc<WebPkiServerVerifier>),
 }
@@ -36,10 +36,11 @@ where
     }
 }
 
-/// an [`Iterator`] over a number of the most significant bits in an unsigned integer
+/// an [`Iterator`] over a number of the most significant bits in an integer
 ///
 /// this goes most significant to least significant. if the range is larger than the number of
-/// bits, it will act like the remaining items are unset bits
+/// bits, it will act like the remaining items are unset bits. signed integers are walked as
+/// their two's complement bit pattern, so the first item is the sign bit
 #[must_use = "iterators do not do anything until consumed"]
 #[derive(Debug, Clone)]
 pub struct BitRangeIter<T> {
@@ -73,13 +74,57 @@ macro_rules! bitrange_impl {
     )*}
 }
 
-bitrange_impl!(u8, u16, u32, u64, u128);
+bitrange_impl!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+/// an [`Iterator`] over a number of the most significant bits in a byte slice
+///
+/// this is like [`BitRangeIter`], going through the bytes in order and each byte from most
+/// significant to least significant. if the range goes past the end of the slice, it will act
+/// like the remaining items are unset bits
+#[must_use = "iterators do not do anything until consumed"]
+#[derive(Debug, Clone)]
+pub struct BitSliceIter<'a> {
+    inner: &'a [u8],
+    pos: usize,
+    range: usize,
+}
+
+impl<'a> From<(&'a [u8], usize)> for BitSliceIter<'a> {
+    fn from(item: (&'a [u8], usize)) -> Self {
+        Self {
+            inner: item.0,
+            pos: 0,
+            range: item.1,
+        }
+    }
+}
+
+impl Iterator for BitSliceIter<'_> {
+    type Item = bool;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.range == 0 {
+            return None;
+        }
+        self.range -= 1;
+
+        let byte = self.inner.get(self.pos / 8).copied().unwrap_or(0);
+        let is_set = byte & 0x80 >> self.pos % 8;
+        self.pos += 1;
+        Some(is_set != 0)
+    }
+}
 
 /// an ip address range
 ///
 /// note that this internally stores ipv4 addresses as ipv4-mapped ipv6 addresses,
 /// which has the side effect of coercing ipv6 addresses in the range `::ffff:0:0/96` to ipv4.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// ranges are ordered by their first address, then by mask length, so a
+/// range comes right before the more specific ranges inside of it. ipv4
+/// ranges sort in with `::ffff:0:0/96`, after `::/96` and before everything
+/// above it. ranges that only differ in the address bits past their mask
+/// are ordered by those last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct IpRange {
     ip: Ipv6Addr,
     mask_len: usize,
@@ -139,10 +184,143 @@ impl IpRange {
         }
     }
 
+    /// the address with everything past the mask cleared
+    const fn network(&self) -> u128 {
+        self.ip.to_bits() & network_mask(self.mask_len)
+    }
+
+    /// split the range into every smaller range with a mask length of
+    /// `new_len`, in order
+    ///
+    /// like [`Self::into_parts`], `new_len` counts from the start of the
+    /// ipv4 address for ipv4 ranges. gives nothing if `new_len` is shorter
+    /// than the range's own mask or too long for its kind of address.
+    /// the ranges are made as they are needed, so splitting a huge range
+    /// is fine as long as you do not try to collect all of it
+    ///
+    /// ```
+    /// # use mark_tree::IpRange;
+    /// let range: IpRange = "10.0.0.0/16".parse().unwrap();
+    /// let mut blocks = range.split(24);
+    /// assert_eq!(blocks.next().unwrap().to_string(), "10.0.0.0/24");
+    /// assert_eq!(blocks.next().unwrap().to_string(), "10.0.1.0/24");
+    /// assert_eq!(blocks.last().unwrap().to_string(), "10.0.255.0/24");
+    /// ```
+    pub fn split(&self, new_len: usize) -> IpRangeSplit {
+        let new_len = match self.into_parts() {
+            (IpAddr::V4(_), _) => new_len.checked_add(96),
+            (IpAddr::V6(_), _) => Some(new_len),
+        }
+        .filter(|&l| l >= self.mask_len && l <= 128);
+
+        let network = self.network();
+        IpRangeSplit {
+            next: new_len.map(|_| network),
+            last: network | !network_mask(self.mask_len),
+            mask_len: new_len.unwrap_or(128),
+        }
+    }
+
     /// create an iterator over the bits in the ip range
     pub fn iter(&self) -> BitRangeIter<u128> {
         (self.ip.to_bits(), self.mask_len).into()
     }
+
+    /// find the fewest ip ranges that cover every address from `start`
+    /// to `end`, inclusive
+    ///
+    /// ipv4 addresses get turned into ipv4-mapped ipv6 addresses, so
+    /// endpoints of different kinds work as you would expect from that.
+    /// gives nothing if `start` comes after `end`
+    #[must_use]
+    pub fn cover(start: IpAddr, end: IpAddr) -> Vec<Self> {
+        let bits = |ip| match ip {
+            IpAddr::V6(ip) => ip.to_bits(),
+            IpAddr::V4(ip) => ip.to_ipv6_mapped().to_bits(),
+        };
+        let (mut start, end) = (bits(start), bits(end));
+        let mut out = vec![];
+
+        while start <= end {
+            // the biggest block that is both aligned to start and does
+            // not go past end
+            let fits = (end - start).checked_add(1).map_or(128, u128::ilog2);
+            let host_len = start.trailing_zeros().min(fits);
+            out.push(Self {
+                ip: Ipv6Addr::from_bits(start),
+                mask_len: 128 - host_len as usize,
+            });
+
+            let last = start | u128::MAX.checked_shr(128 - host_len).unwrap_or(0);
+            let Some(next) = last.checked_add(1) else {
+                break;
+            };
+            start = next;
+        }
+
+        out
+    }
+
+    /// merge ip ranges together into the fewest ranges covering the
+    /// same addresses
+    #[must_use]
+    pub fn aggregate(ranges: &[Self]) -> Vec<Self> {
+        let mut tree: MarkTree = ranges.iter().copied().collect();
+        tree.optimize();
+
+        tree.iter::<Self>()
+            .filter(|(node, _)| **node == MarkTree::AllMarked)
+            .filter_map(|(_, range)| range)
+            .collect()
+    }
+}
+
+/// a mask with the first `mask_len` bits set
+const fn network_mask(mask_len: usize) -> u128 {
+    if mask_len == 0 {
+        0
+    } else {
+        u128::MAX << (128 - mask_len)
+    }
+}
+
+/// an [`Iterator`] over the smaller ranges inside an [`IpRange`], from
+/// [`IpRange::split`]
+#[must_use = "iterators do not do anything until consumed"]
+#[derive(Debug, Clone)]
+pub struct IpRangeSplit {
+    next: Option<u128>,
+    last: u128,
+    mask_len: usize,
+}
+
+impl Iterator for IpRangeSplit {
+    type Item = IpRange;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.next?;
+        let end = start | !network_mask(self.mask_len);
+        self.next = end.checked_add(1).filter(|&n| n <= self.last);
+        Some(IpRange {
+            ip: Ipv6Addr::from_bits(start),
+            mask_len: self.mask_len,
+        })
+    }
+}
+
+impl Ord for IpRange {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.network()
+            .cmp(&other.network())
+            .then(self.mask_len.cmp(&other.mask_len))
+            .then(self.ip.cmp(&other.ip))
+    }
+}
+
+impl PartialOrd for IpRange {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl ConvertBits for IpRange {
@@ -254,6 +432,28 @@ impl MarkTree {
         *self = new;
     }
 
+    /// mark the position in the tree dictated by an iterator, but going
+    /// no deeper than `max_depth`
+    ///
+    /// if the iterator is longer than that, the whole subtree at
+    /// `max_depth` is marked instead. this over-approximates: everything
+    /// that was marked will still be reported by [`Self::is_marked`], but
+    /// so will anything else sharing the first `max_depth` items. in
+    /// exchange, the tree can never grow deeper than `max_depth` from
+    /// marking, no matter how many distinct positions are marked
+    ///
+    /// ```
+    /// # use mark_tree::{BitRangeIter, MarkTree};
+    /// let mut tree = MarkTree::new();
+    /// tree.mark_capped(BitRangeIter::from((0b1011u8, 4)), 2);
+    /// assert!(tree.is_marked(BitRangeIter::from((0b1011u8, 4))));
+    /// // only the first 2 bits were looked at
+    /// assert!(tree.is_marked(BitRangeIter::from((0b1000u8, 4))));
+    /// ```
+    pub fn mark_capped(&mut self, bits: impl Iterator<Item = bool>, max_depth: usize) {
+        self.mark(bits.take(max_depth));
+    }
+
     /// unmark the position in the tree dictated by an iterator
     pub fn unmark(&mut self, mut bits: impl Iterator<Item = bool>) {
         let new = if let Some(bit) = bits.next() {
@@ -310,6 +510,38 @@ impl MarkTree {
         }
     }
 
+    /// check if two trees mark the same positions, even if one has
+    /// not been optimized
+    ///
+    /// unlike `==`, which compares the exact structure, this walks both
+    /// trees at once, so it costs time proportional to the number of
+    /// nodes in both but does not allocate
+    #[must_use]
+    pub fn equivalent(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Branch(a, b), Self::Branch(c, d)) => a.equivalent(c) && b.equivalent(d),
+            // a leaf is the same as a branch with that leaf on both sides
+            (Self::Branch(a, b), leaf) | (leaf, Self::Branch(a, b)) => {
+                a.equivalent(leaf) && b.equivalent(leaf)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// roughly how many bytes the tree has allocated on the heap
+    ///
+    /// this counts the two boxed children of every branch, but not the
+    /// tree itself nor any overhead from the allocator
+    #[must_use]
+    pub fn memory_footprint(&self) -> usize {
+        match self {
+            Self::Branch(a, b) => {
+                2 * size_of::<Self>() + a.memory_footprint() + b.memory_footprint()
+            }
+            Self::AllUnmarked | Self::AllMarked => 0,
+        }
+    }
+
     fn walk(&self, path: &mut Vec<bool>, callback: &mut impl FnMut(&Self, &[bool])) {
         callback(self, path);
 
@@ -330,6 +562,74 @@ impl MarkTree {
         self.walk(&mut vec![], &mut callback);
     }
 
+    fn prune_at(&mut self, path: &mut Vec<bool>, keep: &mut impl FnMut(&[bool], &Self) -> bool) {
+        if !keep(path, self) {
+            *self = Self::AllUnmarked;
+            return;
+        }
+
+        if let Self::Branch(a, b) = self {
+            path.push(false);
+            a.prune_at(path, keep);
+            path.pop();
+            path.push(true);
+            b.prune_at(path, keep);
+            path.pop();
+        }
+    }
+
+    /// walk through the tree, unmarking every subtree the `keep` function
+    /// returns false for
+    ///
+    /// `keep` is passed the path taken to get to a node and the node
+    /// itself. nodes are visited from the root down, and the children of
+    /// a pruned branch are not visited. you may want to call
+    /// [`Self::optimize`] afterwards
+    ///
+    /// ```
+    /// # use mark_tree::{BitRangeIter, MarkTree};
+    /// let mut tree = MarkTree::new();
+    /// tree.mark(BitRangeIter::from((0b1000_0000u8, 2)));
+    /// tree.mark(BitRangeIter::from((0b0110_0000u8, 4)));
+    /// // drop anything marked less than 3 deep
+    /// tree.prune(|path, node| path.len() >= 3 || node != &MarkTree::AllMarked);
+    /// assert!(!tree.is_marked(BitRangeIter::from((0b1000_0000u8, 2))));
+    /// assert!(tree.is_marked(BitRangeIter::from((0b0110_0000u8, 4))));
+    /// ```
+    pub fn prune(&mut self, mut keep: impl FnMut(&[bool], &Self) -> bool) {
+        self.prune_at(&mut vec![], &mut keep);
+    }
+
+    /// create an [`Iterator`] over only the leaves of the tree, with
+    /// whether each is marked
+    ///
+    /// together the leaves cover every position exactly once. a tree
+    /// that is a single leaf gives just that, with an empty path
+    ///
+    /// ```
+    /// # use mark_tree::{IpRange, MarkTree};
+    /// let range: IpRange = "8000::/1".parse().unwrap();
+    /// let tree: MarkTree = [range].into_iter().collect();
+    /// let leaves: Vec<_> = tree
+    ///     .leaves::<IpRange>()
+    ///     .map(|(range, marked)| (range.unwrap().to_string(), marked))
+    ///     .collect();
+    /// assert_eq!(
+    ///     leaves,
+    ///     [("::/1".to_string(), false), ("8000::/1".to_string(), true)],
+    /// );
+    /// ```
+    pub fn leaves<'a, T>(&'a self) -> impl Iterator<Item = (T::Output, bool)> + 'a
+    where
+        T: ConvertBits + 'a,
+    {
+        self.iter::<T>().filter_map(|(node, path)| match node {
+            Self::AllMarked => Some((path, true)),
+            Self::AllUnmarked => Some((path, false)),
+            Self::Branch(..) => None,
+        })
+    }
+
     /// create an [`Iterator`] over the tree
     ///
     /// the generic `T` is the type for expressing the path to the
@@ -348,6 +648,26 @@ impl MarkTree {
     }
 }
 
+/// marks every range, you still need to call [`MarkTree::optimize`]
+/// afterwards for the compacted form
+impl FromIterator<IpRange> for MarkTree {
+    fn from_iter<I: IntoIterator<Item = IpRange>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+/// marks every range, you still need to call [`MarkTree::optimize`]
+/// afterwards for the compacted form
+impl Extend<IpRange> for MarkTree {
+    fn extend<I: IntoIterator<Item = IpRange>>(&mut self, iter: I) {
+        for range in iter {
+            self.mark(range.iter());
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum TreePos {
     Root,
@@ -408,8 +728,8 @@ where
 #[cfg(test)]
 #[allow(clippy::unreadable_literal)]
 mod tests {
-    use crate::{BitRangeIter, ConvertBits, IpRange, MarkTree, ParseIpRangeError};
-    use alloc::{string::ToString, vec::Vec};
+    use crate::{BitRangeIter, BitSliceIter, ConvertBits, IpRange, MarkTree, ParseIpRangeError};
+    use alloc::{collections::BTreeSet, string::ToString, vec::Vec};
     use core::{net::IpAddr, str::FromStr};
 
     #[test]
@@ -429,6 +749,33 @@ mod tests {
         assert_eq!(iter.last(), Some(true));
     }
 
+    #[test]
+    fn range_signed() {
+        assert_eq!(BitRangeIter::from((-5i32, 1)).next(), Some(true));
+        assert_eq!(BitRangeIter::from((5i64, 1)).next(), Some(false));
+        assert_eq!(BitRangeIter::from((i8::MIN, 8)).filter(|&b| b).count(), 1);
+
+        let signed = BitRangeIter::from((-1929445575i32, 40));
+        let unsigned = BitRangeIter::from(((-1929445575i32).cast_unsigned(), 40));
+        assert!(signed.eq(unsigned));
+    }
+
+    #[test]
+    fn range_slice() {
+        let res: Vec<_> = BitSliceIter::from((&b"h"[..], 10)).map(u8::from).collect();
+        assert_eq!(res, [0, 1, 1, 0, 1, 0, 0, 0, 0, 0]);
+
+        let bytes = 7523377975159973992u64.to_be_bytes();
+        let slice = BitSliceIter::from((&bytes[..], 80));
+        assert!(slice.eq(BitRangeIter::from((7523377975159973992u64, 80))));
+
+        assert_eq!(
+            BitSliceIter::from((&[][..], 3)).collect::<Vec<_>>(),
+            [false; 3]
+        );
+        assert_eq!(BitSliceIter::from((&[0xff][..], 0)).count(), 0);
+    }
+
     #[test]
     fn tree_dedup() {
         let mut tree = MarkTree::new();
@@ -491,6 +838,68 @@ mod tests {
         assert!(!tree.is_marked([true, true, false].into_iter()));
     }
 
+    #[test]
+    fn tree_capped() {
+        let ips = [
+            "2001:db8::1",
+            "2001:db8::2",
+            "2001:db8:0:1::1",
+            "2001:db8:ffff::1",
+        ]
+        .map(|ip| IpRange::from_str(ip).unwrap());
+
+        let mut exact = MarkTree::new();
+        let mut capped = MarkTree::new();
+        for ip in &ips {
+            exact.mark(ip.iter());
+            capped.mark_capped(ip.iter(), 64);
+        }
+
+        let mut depth = 0;
+        capped.traverse(|_, path| depth = depth.max(path.len()));
+        assert_eq!(depth, 64);
+
+        // nothing that is truly marked gets lost
+        for ip in &ips {
+            assert!(exact.is_marked(ip.iter()));
+            assert!(capped.is_marked(ip.iter()));
+        }
+        // but the rest of the /64 comes along with it
+        let neighbor = IpRange::from_str("2001:db8::3").unwrap();
+        assert!(!exact.is_marked(neighbor.iter()));
+        assert!(capped.is_marked(neighbor.iter()));
+        let elsewhere = IpRange::from_str("2001:db8:0:2::1").unwrap();
+        assert!(!capped.is_marked(elsewhere.iter()));
+
+        // a cap past the end of the iterator is the same as marking
+        let mut uncapped = MarkTree::new();
+        for ip in &ips {
+            uncapped.mark_capped(ip.iter(), 200);
+        }
+        assert_eq!(uncapped, exact);
+    }
+
+    #[test]
+    fn tree_footprint() {
+        let mut tree = MarkTree::new();
+        assert_eq!(tree.memory_footprint(), 0);
+        tree.mark([true].into_iter());
+        assert_eq!(tree.memory_footprint(), 2 * size_of::<MarkTree>());
+
+        tree.mark(BitRangeIter::from((0u8, 8)));
+        let mut branches = 0;
+        tree.traverse(|node, _| branches += usize::from(matches!(node, MarkTree::Branch(..))));
+        assert_eq!(branches, 8);
+        assert_eq!(
+            tree.memory_footprint(),
+            branches * 2 * size_of::<MarkTree>()
+        );
+
+        tree.mark([false].into_iter());
+        tree.optimize();
+        assert_eq!(tree.memory_footprint(), 0);
+    }
+
     #[test]
     fn iter_traverse() {
         let mut tree = MarkTree::new();
@@ -532,6 +941,258 @@ mod tests {
         );
     }
 
+    #[test]
+    fn range_order() {
+        let mut ranges: Vec<_> = [
+            "2001:db8::/48",
+            "10.0.0.0/8",
+            "::/0",
+            "10.0.0.0/16",
+            "2001:db8::/32",
+            "10.1.2.3/8",
+            "::/96",
+            "9.255.255.255",
+            "0.0.0.0/0",
+        ]
+        .into_iter()
+        .map(|r| IpRange::from_str(r).unwrap())
+        .collect();
+        ranges.sort();
+        assert_eq!(
+            ranges.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "::/0",
+                "::/96",
+                "0.0.0.0/0",
+                "9.255.255.255/32",
+                "10.0.0.0/8",
+                "10.1.2.3/8",
+                "10.0.0.0/16",
+                "2001:db8::/32",
+                "2001:db8::/48",
+            ]
+        );
+
+        let set: BTreeSet<_> = ranges.iter().rev().chain(&ranges).copied().collect();
+        assert!(set.into_iter().eq(ranges));
+    }
+
+    #[test]
+    fn range_cover() {
+        let cover = |start, end| {
+            IpRange::cover(
+                IpAddr::from_str(start).unwrap(),
+                IpAddr::from_str(end).unwrap(),
+            )
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(cover("1.2.3.4", "1.2.3.4"), ["1.2.3.4/32"]);
+        assert_eq!(cover("fe80::1", "fe80::1"), ["fe80::1/128"]);
+        assert_eq!(cover("0.0.0.0", "255.255.255.255"), ["0.0.0.0/0"]);
+        assert_eq!(
+            cover("::", "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"),
+            ["::/0"]
+        );
+        assert_eq!(
+            cover("10.0.0.1", "10.0.0.10"),
+            [
+                "10.0.0.1/32",
+                "10.0.0.2/31",
+                "10.0.0.4/30",
+                "10.0.0.8/31",
+                "10.0.0.10/32"
+            ]
+        );
+        assert_eq!(
+            cover("::fffe:ffff:ffff", "0.0.0.1"),
+            ["::fffe:ffff:ffff/128", "0.0.0.0/31"]
+        );
+        assert!(cover("10.0.0.2", "10.0.0.1").is_empty());
+    }
+
+    #[test]
+    fn range_aggregate() {
+        let aggregate = |ranges: &[&str]| {
+            let ranges: Vec<_> = ranges
+                .iter()
+                .map(|r| IpRange::from_str(r).unwrap())
+                .collect();
+            IpRange::aggregate(&ranges)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            aggregate(&["10.0.0.128/25", "10.0.0.0/25"]),
+            ["10.0.0.0/24"]
+        );
+        assert_eq!(
+            aggregate(&["10.0.0.0/8", "10.1.2.3", "fe80::/10", "fe80::1"]),
+            ["10.0.0.0/8", "fe80::/10"]
+        );
+        // ipv4-mapped ipv6 addresses are the same as ipv4
+        assert_eq!(
+            aggregate(&["::ffff:192.168.0.0/121", "192.168.0.128/25"]),
+            ["192.168.0.0/24"]
+        );
+        assert!(aggregate(&[]).is_empty());
+    }
+
+    #[test]
+    fn collect_ranges() {
+        let ranges: Vec<IpRange> = ["10.0.0.0/8", "192.168.0.0/24", "2001:db8::/32"]
+            .iter()
+            .map(|r| r.parse().unwrap())
+            .collect();
+
+        let mut manual = MarkTree::new();
+        for range in &ranges {
+            manual.mark(range.iter());
+        }
+
+        let collected: MarkTree = ranges.iter().copied().collect();
+        assert_eq!(collected, manual);
+
+        let mut extended: MarkTree = ranges[..1].iter().copied().collect();
+        extended.extend(ranges[1..].iter().copied());
+        assert_eq!(extended, manual);
+    }
+
+    #[test]
+    fn equivalent_trees() {
+        let mut halves = MarkTree::new();
+        halves.mark([true, false].into_iter());
+        halves.mark([true, true].into_iter());
+        let mut whole = MarkTree::new();
+        whole.mark([true].into_iter());
+
+        assert_ne!(halves, whole);
+        assert!(halves.equivalent(&whole));
+        assert!(whole.equivalent(&halves));
+
+        halves.unmark([true, true, false].into_iter());
+        assert!(!halves.equivalent(&whole));
+        halves.mark([true, true, false].into_iter());
+        assert!(halves.equivalent(&whole));
+
+        let mut unmarked = MarkTree::new();
+        unmarked.mark([false].into_iter());
+        unmarked.unmark([false].into_iter());
+        assert!(unmarked.equivalent(&MarkTree::new()));
+        assert!(!unmarked.equivalent(&whole));
+    }
+
+    #[test]
+    fn tree_prune() {
+        let ranges = ["10.0.0.0/8", "172.16.0.0/12", "192.0.0.0/3", "64.0.0.0/4"];
+        let mut tree: MarkTree = ranges
+            .iter()
+            .map(|r| r.parse::<IpRange>().unwrap())
+            .collect();
+        tree.optimize();
+
+        // drop anything broader than a /8, ipv4 is mapped 96 bits deep
+        tree.prune(|path, node| path.len() >= 96 + 8 || !matches!(node, MarkTree::AllMarked));
+
+        let marked = |r: &str| tree.is_marked(r.parse::<IpRange>().unwrap().iter());
+        assert!(marked("10.0.0.0/8"));
+        assert!(marked("10.20.0.0/16"));
+        assert!(marked("172.16.0.0/12"));
+        assert!(!marked("192.0.0.0/3"));
+        assert!(!marked("200.0.0.0/8"));
+        assert!(!marked("64.0.0.0/4"));
+        assert!(!marked("70.0.0.0/8"));
+
+        // pruning everything below the root leaves nothing
+        tree.prune(|path, _| path.is_empty());
+        assert!(tree.equivalent(&MarkTree::new()));
+
+        // pruning the root itself
+        let mut whole = MarkTree::AllMarked;
+        whole.prune(|_, _| false);
+        assert_eq!(whole, MarkTree::AllUnmarked);
+    }
+
+    #[test]
+    fn tree_leaves() {
+        let leaves = |tree: &MarkTree| -> Vec<_> {
+            tree.leaves::<IpRange>()
+                .map(|(range, marked)| (range.unwrap().to_string(), marked))
+                .collect()
+        };
+
+        assert_eq!(leaves(&MarkTree::new()), [("::/0".to_string(), false)]);
+        assert_eq!(leaves(&MarkTree::AllMarked), [("::/0".to_string(), true)]);
+
+        let mut tree: MarkTree = ["10.0.0.0/8", "11.0.0.0/8"]
+            .iter()
+            .map(|r| r.parse::<IpRange>().unwrap())
+            .collect();
+        tree.optimize();
+        let all = leaves(&tree);
+        let marked: Vec<_> = all
+            .iter()
+            .filter(|(_, m)| *m)
+            .map(|(r, _)| r.as_str())
+            .collect();
+        assert_eq!(marked, ["10.0.0.0/7"]);
+        // every other leaf is unmarked, one per level down to the /7
+        assert_eq!(all.len(), 96 + 7 + 1);
+        assert_eq!(all.first().unwrap(), &("::/81".to_string(), false));
+        assert_eq!(all.last().unwrap(), &("8000::/1".to_string(), false));
+
+        // the leaves cover the whole space exactly once
+        let total: u128 = all
+            .iter()
+            .map(|(r, _)| {
+                let len = r.parse::<IpRange>().unwrap().iter().count();
+                1u128 << (127 - len)
+            })
+            .sum();
+        assert_eq!(total, 1 << 127);
+    }
+
+    #[test]
+    fn range_split() {
+        let range = IpRange::from_str("192.0.2.4/30").unwrap();
+        let split: Vec<_> = range.split(32).map(|r| r.to_string()).collect();
+        assert_eq!(
+            split,
+            [
+                "192.0.2.4/32",
+                "192.0.2.5/32",
+                "192.0.2.6/32",
+                "192.0.2.7/32"
+            ]
+        );
+
+        // splitting into the same length gives the range back, without
+        // any bits past the mask
+        let range = IpRange::from_str("2001:db8::1/32").unwrap();
+        assert_eq!(
+            range.split(32).map(|r| r.to_string()).collect::<Vec<_>>(),
+            ["2001:db8::/32"]
+        );
+        assert_eq!(range.split(31).count(), 0);
+        assert_eq!(range.split(129).count(), 0);
+        assert_eq!(range.split(36).count(), 16);
+        assert_eq!(
+            IpRange::from_str("10.0.0.0/8").unwrap().split(33).count(),
+            0
+        );
+
+        // lazy enough to not care how many there are
+        let mut all = IpRange::from_str("::/0").unwrap().split(128);
+        assert_eq!(all.next().unwrap().to_string(), "::/128");
+        assert_eq!(all.next().unwrap().to_string(), "::1/128");
+        let whole = IpRange::from_str("::/0").unwrap();
+        assert_eq!(whole.split(0).collect::<Vec<_>>(), [whole]);
+        let last = IpRange::from_str("ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/127").unwrap();
+        assert_eq!(last.split(128).count(), 2);
+    }
+
     #[test]
     fn range_bits() {
         assert_eq!(
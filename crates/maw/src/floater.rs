@@ -3,16 +3,26 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use half::{bf16, f16};
+use std::fmt::Write;
 
 /// show the error for floats
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand, name = "floater")]
 #[argh(help_triggers("-h", "--help"))]
 pub struct Args {
+    /// only print the gap to the next larger float
+    #[argh(switch)]
+    terse: bool,
+    /// also print the exact value that gets stored
+    #[argh(switch)]
+    exact: bool,
+    /// the raw bits of the float in hex, instead of a number
+    #[argh(option)]
+    bits: Option<String>,
     #[argh(positional)]
     size: Size,
     #[argh(positional)]
-    number: f64,
+    number: Option<f64>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -38,31 +48,269 @@ impl std::str::FromStr for Size {
     }
 }
 
+/// step a 16 bit float to its neighbor, half does not have `next_up`
+/// and `next_down`
+fn step16(bits: u16, up: bool) -> u16 {
+    const SIGN: u16 = 0x8000;
+    let magnitude = bits & !SIGN;
+    let negative = bits & SIGN != 0;
+    if magnitude == 0 {
+        // both zeros step to the smallest subnormal in either direction
+        return if up { 1 } else { SIGN | 1 };
+    }
+    if up == negative { bits - 1 } else { bits + 1 }
+}
+
+/// the stored value of the number along with the next smaller and
+/// next larger values, widened to f64
+fn neighbors(size: Size, number: f64) -> (f64, f64, f64) {
+    match size {
+        Size::F64 => (number.next_down(), number, number.next_up()),
+        Size::F32 => {
+            #[allow(clippy::cast_possible_truncation)]
+            let float = number as f32;
+            (
+                float.next_down().into(),
+                float.into(),
+                float.next_up().into(),
+            )
+        }
+        Size::F16 => {
+            let float = f16::from_f64(number);
+            let step = |up| {
+                if float.is_nan() || float.is_infinite() && float.is_sign_positive() == up {
+                    float
+                } else {
+                    f16::from_bits(step16(float.to_bits(), up))
+                }
+            };
+            (step(false).into(), float.into(), step(true).into())
+        }
+        Size::BF16 => {
+            let float = bf16::from_f64(number);
+            let step = |up| {
+                if float.is_nan() || float.is_infinite() && float.is_sign_positive() == up {
+                    float
+                } else {
+                    bf16::from_bits(step16(float.to_bits(), up))
+                }
+            };
+            (step(false).into(), float.into(), step(true).into())
+        }
+    }
+}
+
+/// turn a hex bit pattern into the float it stands for, widened to f64
+fn from_bits(size: Size, bits: &str) -> Result<f64, &'static str> {
+    let hex = bits
+        .strip_prefix("0x")
+        .or_else(|| bits.strip_prefix("0X"))
+        .unwrap_or(bits);
+    let bits = u64::from_str_radix(hex, 16).map_err(|_| "bits should be a hex number")?;
+    let narrow = |bits| u16::try_from(bits).map_err(|_| "bits do not fit in 16 bits");
+    Ok(match size {
+        Size::F64 => f64::from_bits(bits),
+        Size::F32 => {
+            f32::from_bits(u32::try_from(bits).map_err(|_| "bits do not fit in 32 bits")?).into()
+        }
+        Size::F16 => f16::from_bits(narrow(bits)?).into(),
+        Size::BF16 => bf16::from_bits(narrow(bits)?).into(),
+    })
+}
+
+/// multiply a little endian base 1e9 number in place
+fn mul_small(limbs: &mut Vec<u32>, n: u64) {
+    let base = 1_000_000_000;
+    let mut carry = 0;
+    for limb in limbs.iter_mut() {
+        let product = u128::from(*limb) * u128::from(n) + carry;
+        *limb = u32::try_from(product % base).unwrap();
+        carry = product / base;
+    }
+    while carry > 0 {
+        limbs.push(u32::try_from(carry % base).unwrap());
+        carry /= base;
+    }
+}
+
+/// write out every decimal digit of a float, since every finite float
+/// is a dyadic fraction its expansion always ends
+fn exact(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let bits = value.to_bits();
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let exponent = i32::try_from((bits >> 52) & 0x7ff).unwrap();
+    let fraction = bits & ((1 << 52) - 1);
+    let (mut mantissa, mut exponent) = if exponent == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | 1 << 52, exponent - 1075)
+    };
+    if mantissa == 0 {
+        return format!("{sign}0");
+    }
+    while mantissa % 2 == 0 && exponent < 0 {
+        mantissa /= 2;
+        exponent += 1;
+    }
+
+    let mut limbs = vec![1];
+    mul_small(&mut limbs, mantissa);
+    // m * 2^-k is the same as m * 5^k / 10^k
+    let factor = if exponent < 0 { 5 } else { 2 };
+    for _ in 0..exponent.unsigned_abs() {
+        mul_small(&mut limbs, factor);
+    }
+    while limbs.len() > 1 && limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+
+    let mut digits = limbs.last().unwrap().to_string();
+    for limb in limbs.iter().rev().skip(1) {
+        write!(digits, "{limb:09}").unwrap();
+    }
+    if exponent >= 0 {
+        return format!("{sign}{digits}");
+    }
+    let decimals = exponent.unsigned_abs() as usize;
+    if digits.len() <= decimals {
+        digits.insert_str(0, &"0".repeat(decimals + 1 - digits.len()));
+    }
+    let (whole, decimal) = digits.split_at(digits.len() - decimals);
+    format!("{sign}{whole}.{decimal}")
+}
+
 pub fn run(args: &Args) {
-    println!(
-        "{}",
-        match args.size {
-            Size::F64 => {
-                let float = args.number;
-                let new = f64::from_bits(float.to_bits() + 1);
-                new - float
+    let number = match (args.number, &args.bits) {
+        (Some(number), None) => number,
+        (None, Some(bits)) => match from_bits(args.size, bits) {
+            Ok(number) => {
+                if !args.terse {
+                    println!("value: {number}");
+                }
+                number
             }
-            Size::F32 => {
-                #[allow(clippy::cast_possible_truncation)]
-                let float = args.number as f32;
-                let new = f32::from_bits(float.to_bits() + 1);
-                (new - float).into()
-            }
-            Size::F16 => {
-                let float = f16::from_f64(args.number);
-                let new = f16::from_bits(float.to_bits() + 1);
-                (new - float).into()
-            }
-            Size::BF16 => {
-                let float = bf16::from_f64(args.number);
-                let new = bf16::from_bits(float.to_bits() + 1);
-                (new - float).into()
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
             }
+        },
+        _ => {
+            eprintln!("give either a number or --bits");
+            std::process::exit(1);
+        }
+    };
+    let (below, value, above) = neighbors(args.size, number);
+    let ulp = above - value;
+    if args.terse {
+        println!("{ulp}");
+        if args.exact {
+            println!("{}", exact(value));
         }
+        return;
+    }
+    if args.exact {
+        println!("exact: {}", exact(value));
+    }
+    println!("above: {ulp}");
+    println!("below: {}", value - below);
+    println!("relative: {}", ulp / value.abs());
+}
+
+#[test]
+fn bit_patterns() {
+    assert_eq!(from_bits(Size::F32, "0x3f800000"), Ok(1.0));
+    assert_eq!(from_bits(Size::F64, "3ff8000000000000"), Ok(1.5));
+    assert_eq!(from_bits(Size::F16, "0xc000"), Ok(-2.0));
+    assert_eq!(from_bits(Size::BF16, "0x3f80"), Ok(1.0));
+    assert!(from_bits(Size::F16, "0x3f800000").is_err());
+    assert!(from_bits(Size::F32, "0x1ffffffff").is_err());
+    assert!(from_bits(Size::F32, "meow").is_err());
+}
+
+#[test]
+fn exact_digits() {
+    assert_eq!(
+        exact(0.1),
+        "0.1000000000000000055511151231257827021181583404541015625"
     );
+    assert_eq!(exact(1.5), "1.5");
+    assert_eq!(exact(-0.0), "-0");
+    assert_eq!(exact(0.0), "0");
+    // above 2^53 floats are whole numbers with gaps between them
+    assert_eq!(exact(2f64.powi(60) + 256.0), "1152921504606847232");
+    assert_eq!(exact(1e23), "99999999999999991611392");
+    assert_eq!(exact(f64::NEG_INFINITY), "-inf");
+
+    // the smallest subnormal is 2^-1074, which has 1074 decimals
+    let tiny = exact(5e-324);
+    let (whole, decimals) = tiny.split_once('.').unwrap();
+    assert_eq!(whole, "0");
+    assert_eq!(decimals.len(), 1074);
+    assert_eq!(decimals.trim_start_matches('0').len(), 1074 - 323);
+    assert!(decimals[323..].starts_with("494065645841246"));
+    assert!(decimals.ends_with("3447265625"));
+    assert_eq!(exact(-5e-324), format!("-{tiny}"));
+}
+
+#[test]
+fn neighbors_f32() {
+    for x in [
+        0.0,
+        -0.0,
+        1.0,
+        -1.0,
+        -3.5e-40,
+        f32::MAX,
+        f32::MIN,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+    ] {
+        let (below, value, above) = neighbors(Size::F32, x.into());
+        assert_eq!(below.to_bits(), f64::from(x.next_down()).to_bits(), "{x}");
+        assert_eq!(value.to_bits(), f64::from(x).to_bits(), "{x}");
+        assert_eq!(above.to_bits(), f64::from(x.next_up()).to_bits(), "{x}");
+    }
+}
+
+#[test]
+fn neighbors_16() {
+    let tiny = 2f64.powi(-24);
+    assert_eq!(neighbors(Size::F16, 0.0), (-tiny, 0.0, tiny));
+    assert_eq!(neighbors(Size::F16, -0.0), (-tiny, 0.0, tiny));
+    assert_eq!(
+        neighbors(Size::F16, -1.0),
+        (-1.0 - 2f64.powi(-10), -1.0, -1.0 + 2f64.powi(-11))
+    );
+    assert_eq!(
+        neighbors(Size::F16, 65504.0),
+        (65472.0, 65504.0, f64::INFINITY)
+    );
+    assert_eq!(
+        neighbors(Size::F16, f64::NEG_INFINITY),
+        (f64::NEG_INFINITY, f64::NEG_INFINITY, -65504.0)
+    );
+
+    // compare every bit pattern against the neighbors found by sorting
+    // all of them
+    let check = |size, decode: fn(u16) -> f64| {
+        let mut sorted: Vec<f64> = (0..=u16::MAX).map(decode).filter(|f| !f.is_nan()).collect();
+        sorted.sort_by(f64::total_cmp);
+        sorted.dedup();
+        for bits in 0..=u16::MAX {
+            let x = decode(bits);
+            if x.is_nan() {
+                continue;
+            }
+            let after = sorted.partition_point(|&f| f <= x);
+            let before = sorted.partition_point(|&f| f < x);
+            let above = sorted.get(after).copied().unwrap_or(x);
+            let below = before.checked_sub(1).map_or(x, |i| sorted[i]);
+            assert_eq!(neighbors(size, x), (below, x, above), "{bits:04x}");
+        }
+    };
+    check(Size::F16, |b| f16::from_bits(b).into());
+    check(Size::BF16, |b| bf16::from_bits(b).into());
 }
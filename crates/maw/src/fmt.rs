@@ -7,8 +7,8 @@ use std::{
     convert::From,
     fmt,
     fs::File,
-    io::{Read, Seek, Write},
-    path::PathBuf,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
     process::exit,
 };
 
@@ -16,6 +16,9 @@ use std::{
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand, name = "fmt")]
 #[argh(help_triggers("-h", "--help"))]
+#[argh(error_code(1, "some files are not formatted, with --check"))]
+#[argh(error_code(2, "some files could not be read or written"))]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Args {
     /// check if formatted
     #[argh(switch, short = 'c')]
@@ -23,10 +26,36 @@ pub struct Args {
     /// overwrite with formatted
     #[argh(switch, short = 'f')]
     fix: bool,
+    /// sort keys alphabetically
+    #[argh(switch, short = 's')]
+    sort_keys: bool,
+    /// indent continuation lines by this many columns past the margin
+    #[argh(option, short = 'i')]
+    indent: Option<usize>,
+    /// line values up with tabs instead of spaces
+    #[argh(switch, short = 't')]
+    tabs: bool,
     #[argh(positional, greedy)]
     files: Vec<PathBuf>,
 }
 
+const TAB_WIDTH: usize = 8;
+
+/// the column after some whitespace starting at `start`. tabs are only
+/// expanded with --tabs, otherwise they count as one column like before
+fn expand_tabs(start: usize, whitespace: &str, tabs: bool) -> usize {
+    if !tabs {
+        return start + whitespace.len();
+    }
+    whitespace.chars().fold(start, |col, c| {
+        if c == '\t' {
+            (col + 1).next_multiple_of(TAB_WIDTH)
+        } else {
+            col + 1
+        }
+    })
+}
+
 #[derive(Debug)]
 struct InfoLine {
     key: Option<String>,
@@ -36,13 +65,13 @@ struct InfoLine {
 
 impl InfoLine {
     #[allow(clippy::cast_possible_wrap)]
-    fn parse_line(inp: &str, prev: isize) -> (Self, isize) {
+    fn parse_line(inp: &str, prev: isize, tabs: bool) -> (Self, isize) {
         let (_, key, whitespace, value) =
             regex_captures!(r"^(?:([^ \t]*):)?([ \t]*)((?:[^ \t].*)?)$", inp).unwrap();
         let (new, indent) = if key.is_empty() {
-            (prev, whitespace.len() as isize - prev)
+            (prev, expand_tabs(0, whitespace, tabs) as isize - prev)
         } else {
-            ((key.len() + whitespace.len() + 1) as isize, 0)
+            (expand_tabs(key.len() + 1, whitespace, tabs) as isize, 0)
         };
         let out = Self {
             key: if key.is_empty() {
@@ -61,8 +90,16 @@ impl InfoLine {
     }
 }
 
+#[derive(Debug, Default)]
+struct Style {
+    /// fixed indent for continuation lines, instead of keeping theirs
+    indent: Option<usize>,
+    tabs: bool,
+}
+
 struct InfoFile {
     lines: Vec<InfoLine>,
+    style: Style,
 }
 
 impl InfoFile {
@@ -73,18 +110,42 @@ impl InfoFile {
             .map(|k| k.len() + 2)
             .max()
     }
+
+    /// sort the keyed lines, bringing their continuation lines along
+    fn sort_keys(&mut self) {
+        let first_key = self
+            .lines
+            .iter()
+            .position(|l| l.key.is_some())
+            .unwrap_or(self.lines.len());
+        let mut groups: Vec<Vec<InfoLine>> = vec![];
+        for line in self.lines.drain(first_key..) {
+            match groups.last_mut() {
+                Some(group) if line.key.is_none() => group.push(line),
+                _ => groups.push(vec![line]),
+            }
+        }
+        groups.sort_by(|a, b| a[0].key.cmp(&b[0].key));
+        self.lines.extend(groups.into_iter().flatten());
+    }
 }
 
-impl<T: AsRef<str>> From<T> for InfoFile {
-    fn from(inp: T) -> Self {
+impl InfoFile {
+    fn parse(inp: &str, style: Style) -> Self {
         let mut wlen = isize::MAX;
         let mut lines = Vec::new();
-        for line in inp.as_ref().lines() {
-            let (parsed, new) = InfoLine::parse_line(line, wlen);
+        for line in inp.lines() {
+            let (parsed, new) = InfoLine::parse_line(line, wlen, style.tabs);
             lines.push(parsed);
             wlen = new;
         }
-        Self { lines }
+        Self { lines, style }
+    }
+}
+
+impl<T: AsRef<str>> From<T> for InfoFile {
+    fn from(inp: T) -> Self {
+        Self::parse(inp.as_ref(), Style::default())
     }
 }
 
@@ -92,7 +153,10 @@ impl fmt::Display for InfoFile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use std::fmt::Write;
 
-        let margin = self.margin().unwrap_or(0);
+        let mut margin = self.margin().unwrap_or(0);
+        if self.style.tabs {
+            margin = margin.next_multiple_of(TAB_WIDTH);
+        }
         for line in &self.lines {
             let mut written = 0;
 
@@ -103,7 +167,17 @@ impl fmt::Display for InfoFile {
             }
 
             if let Some(v) = &line.value {
-                while written < margin + line.indent {
+                let indent = match (&line.key, self.style.indent) {
+                    (None, Some(indent)) => indent,
+                    _ => line.indent,
+                };
+                if self.style.tabs {
+                    while (written + 1).next_multiple_of(TAB_WIDTH) <= margin + indent {
+                        f.write_char('\t')?;
+                        written = (written + 1).next_multiple_of(TAB_WIDTH);
+                    }
+                }
+                while written < margin + indent {
                     f.write_char(' ')?;
                     written += 1;
                 }
@@ -116,34 +190,133 @@ impl fmt::Display for InfoFile {
     }
 }
 
+/// format one file, giving whether it was not already formatted
+fn fmt_file(name: &Path, args: &Args) -> io::Result<bool> {
+    let mut file = File::options().read(true).write(args.fix).open(name)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let mut parsed = InfoFile::parse(
+        &contents,
+        Style {
+            indent: args.indent,
+            tabs: args.tabs,
+        },
+    );
+    if args.sort_keys {
+        parsed.sort_keys();
+    }
+    let formatted = parsed.to_string();
+    let differs = contents != formatted;
+    if args.check {
+        // only report
+    } else if args.fix {
+        if differs {
+            file.set_len(0)?;
+            file.rewind()?;
+            file.write_all(formatted.as_bytes())?;
+        }
+    } else {
+        print!("{formatted}");
+    }
+    Ok(differs)
+}
+
 pub fn run(args: &Args) {
-    let mut ret = 0;
     let files = if args.files.is_empty() {
         &vec![PathBuf::from("/dev/stdin")]
     } else {
         &args.files
     };
+    let mut differing = 0;
+    let mut failed = false;
     for name in files {
-        let mut file = File::options()
-            .read(true)
-            .write(args.fix)
-            .open(name)
-            .unwrap();
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        let parsed = InfoFile::from(&contents);
-        if args.check {
-            if contents != parsed.to_string() {
+        match fmt_file(name, args) {
+            Ok(true) if args.check => {
                 eprintln!("{} differs", name.display());
-                ret = 1;
+                differing += 1;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("{}: {e}", name.display());
+                failed = true;
             }
-        } else if args.fix {
-            file.set_len(0).unwrap();
-            file.rewind().unwrap();
-            write!(file, "{parsed}").unwrap();
-        } else {
-            print!("{parsed}");
         }
     }
-    exit(ret);
+    if differing > 0 {
+        eprintln!("{differing} of {} files differ", files.len());
+    }
+    if failed {
+        exit(2);
+    }
+    if differing > 0 {
+        exit(1);
+    }
+}
+
+#[test]
+fn fmt_files() {
+    use argh::FromArgs;
+
+    let dir = std::env::temp_dir().join(format!("maw-fmt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let formatted = dir.join("formatted.info");
+    std::fs::write(&formatted, "a:   1\nbbb: 2\n").unwrap();
+    let messy = dir.join("messy.info");
+    std::fs::write(&messy, "a: 1\nbbb: 2\n").unwrap();
+
+    let check = Args::from_args(&["fmt"], &["--check"]).unwrap();
+    assert!(!fmt_file(&formatted, &check).unwrap());
+    assert!(fmt_file(&messy, &check).unwrap());
+    assert!(fmt_file(&dir.join("missing.info"), &check).is_err());
+
+    let fix = Args::from_args(&["fmt"], &["--fix"]).unwrap();
+    assert!(fmt_file(&messy, &fix).unwrap());
+    assert!(!fmt_file(&messy, &check).unwrap());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn styles() {
+    use argh::FromArgs;
+
+    let dir = std::env::temp_dir().join(format!("maw-fmt-styles-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let messy = "a: 1\n\tmore\nbbb:\t2\n";
+    for (flags, expected) in [
+        // tabs count as one column unless --tabs is given
+        (&[][..], "a:   1\n     more\nbbb: 2\n"),
+        (&["--indent", "2"], "a:   1\n       more\nbbb: 2\n"),
+        (&["--tabs"], "a:\t1\n\t     more\nbbb:\t2\n"),
+        (&["--tabs", "--indent", "4"], "a:\t1\n\t    more\nbbb:\t2\n"),
+    ] {
+        let file = dir.join("styled.info");
+        std::fs::write(&file, messy).unwrap();
+        let fix = Args::from_args(&["fmt"], &[&["--fix"], flags].concat()).unwrap();
+        let check = Args::from_args(&["fmt"], &[&["--check"], flags].concat()).unwrap();
+        assert!(fmt_file(&file, &check).unwrap(), "{flags:?}");
+        assert!(fmt_file(&file, &fix).unwrap(), "{flags:?}");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), expected);
+        assert!(!fmt_file(&file, &check).unwrap(), "{flags:?}");
+    }
+
+    // --check compares against the configured style
+    let file = dir.join("spaces.info");
+    std::fs::write(&file, "a:   1\nbbb: 2\n").unwrap();
+    let check = Args::from_args(&["fmt"], &["--check"]).unwrap();
+    assert!(!fmt_file(&file, &check).unwrap());
+    let check = Args::from_args(&["fmt"], &["--check", "--tabs"]).unwrap();
+    assert!(fmt_file(&file, &check).unwrap());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sorted_keys() {
+    let mut parsed = InfoFile::from("  preamble\nzed: 1\n     more\nabc: 2\nmid: 3\n     also\n");
+    parsed.sort_keys();
+    assert_eq!(
+        parsed.to_string(),
+        "     preamble\nabc: 2\nmid: 3\n     also\nzed: 1\n     more\n"
+    );
 }
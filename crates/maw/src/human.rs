@@ -6,15 +6,26 @@
 
 use std::fmt;
 
-/// convert numbers to binary prefixes
+/// convert numbers to binary or decimal prefixes
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand, name = "human")]
 #[argh(help_triggers("-h", "--help"))]
 pub struct Args {
+    /// parse a prefixed number like 1.5Gi back into a plain one
+    #[argh(switch)]
+    reverse: bool,
+    /// use decimal prefixes (powers of 1000) instead of binary ones
+    #[argh(switch)]
+    si: bool,
+    /// how many decimal places to show, at most 8
+    #[argh(option, default = "0")]
+    precision: u32,
+    /// the number to convert, put -- before negative ones
     #[argh(positional)]
-    number: u128,
+    number: String,
 }
 
+#[derive(Clone, Copy)]
 enum Prefix {
     None,
     Ki,
@@ -27,18 +38,30 @@ enum Prefix {
     Yi,
     Ri,
     Qi,
+    K,
+    M,
+    G,
+    T,
+    P,
+    E,
+    Z,
+    Y,
+    R,
+    Q,
+}
+
+macro_rules! leggies {
+    ($num:expr, $(($prefix:ident, $min:expr)),*) => {
+        match $num {
+            $($min.. => ($min, Prefix::$prefix),)*
+            _ => (1, Prefix::None),
+        }
+    }
 }
 
 impl Prefix {
+    /// the largest prefix that fits, along with what to divide by
     fn get(num: u128) -> (u128, Self) {
-        macro_rules! leggies {
-            ($num:expr, $(($prefix:ident, $min:expr)),*) => {
-                match $num {
-                    $($min.. => ($num/$min, Prefix::$prefix),)*
-                    _ => ($num, Prefix::None),
-                }
-            }
-        }
         leggies!(
             num,
             (Qi, 1267650600228229401496703205376),
@@ -53,6 +76,84 @@ impl Prefix {
             (Ki, 1024)
         )
     }
+
+    fn get_si(num: u128) -> (u128, Self) {
+        leggies!(
+            num,
+            (Q, 1000000000000000000000000000000),
+            (R, 1000000000000000000000000000),
+            (Y, 1000000000000000000000000),
+            (Z, 1000000000000000000000),
+            (E, 1000000000000000000),
+            (P, 1000000000000000),
+            (T, 1000000000000),
+            (G, 1000000000),
+            (M, 1000000),
+            (K, 1000)
+        )
+    }
+
+    /// what the prefix multiplies by, as base^power
+    fn scale(self) -> (u128, u32) {
+        match self {
+            Self::None => (1, 0),
+            Self::Ki => (1024, 1),
+            Self::Mi => (1024, 2),
+            Self::Gi => (1024, 3),
+            Self::Ti => (1024, 4),
+            Self::Pi => (1024, 5),
+            Self::Ei => (1024, 6),
+            Self::Zi => (1024, 7),
+            Self::Yi => (1024, 8),
+            Self::Ri => (1024, 9),
+            Self::Qi => (1024, 10),
+            Self::K => (1000, 1),
+            Self::M => (1000, 2),
+            Self::G => (1000, 3),
+            Self::T => (1000, 4),
+            Self::P => (1000, 5),
+            Self::E => (1000, 6),
+            Self::Z => (1000, 7),
+            Self::Y => (1000, 8),
+            Self::R => (1000, 9),
+            Self::Q => (1000, 10),
+        }
+    }
+}
+
+impl std::str::FromStr for Prefix {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "" => Self::None,
+            "Ki" => Self::Ki,
+            "Mi" => Self::Mi,
+            "Gi" => Self::Gi,
+            "Ti" => Self::Ti,
+            "Pi" => Self::Pi,
+            "Ei" => Self::Ei,
+            "Zi" => Self::Zi,
+            "Yi" => Self::Yi,
+            "Ri" => Self::Ri,
+            "Qi" => Self::Qi,
+            "k" => Self::K,
+            "M" => Self::M,
+            "G" => Self::G,
+            "T" => Self::T,
+            "P" => Self::P,
+            "E" => Self::E,
+            "Z" => Self::Z,
+            "Y" => Self::Y,
+            "R" => Self::R,
+            "Q" => Self::Q,
+            _ => {
+                return Err(
+                    "prefix should be one of Ki, Mi, Gi, Ti, Pi, Ei, Zi, Yi, Ri, Qi, \
+                     or k, M, G, T, P, E, Z, Y, R, Q",
+                );
+            }
+        })
+    }
 }
 
 impl fmt::Display for Prefix {
@@ -69,11 +170,167 @@ impl fmt::Display for Prefix {
             Self::Yi => f.write_str("Yi"),
             Self::Ri => f.write_str("Ri"),
             Self::Qi => f.write_str("Qi"),
+            Self::K => f.write_str("k"),
+            Self::M => f.write_str("M"),
+            Self::G => f.write_str("G"),
+            Self::T => f.write_str("T"),
+            Self::P => f.write_str("P"),
+            Self::E => f.write_str("E"),
+            Self::Z => f.write_str("Z"),
+            Self::Y => f.write_str("Y"),
+            Self::R => f.write_str("R"),
+            Self::Q => f.write_str("Q"),
         }
     }
 }
 
+/// how many digits after the decimal point we bother looking at,
+/// any more and scaling them up could overflow
+const MAX_DECIMALS: usize = 35;
+
+/// turn a prefixed number back into a plain one, rounding to the
+/// nearest integer
+fn parse_prefixed(s: &str) -> Result<u128, &'static str> {
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (mantissa, prefix) = s.split_at(split);
+    let prefix: Prefix = prefix.parse()?;
+    let (whole, decimal) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && decimal.is_empty()
+        || !whole
+            .bytes()
+            .chain(decimal.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err("number should look like 1.5Gi");
+    }
+    let decimal = &decimal[..decimal.len().min(MAX_DECIMALS)];
+
+    let overflow = "number is too big";
+    let (base, power) = prefix.scale();
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| overflow)?
+    };
+    let whole = base
+        .checked_pow(power)
+        .and_then(|m| whole.checked_mul(m))
+        .ok_or(overflow)?;
+
+    // scale the fractional part up one prefix at a time, so that it
+    // never gets bigger than base * 10^MAX_DECIMALS
+    let one = 10_u128.pow(u32::try_from(decimal.len()).unwrap());
+    let mut fraction: u128 = if decimal.is_empty() {
+        0
+    } else {
+        decimal.parse().unwrap()
+    };
+    let mut scaled: u128 = 0;
+    for _ in 0..power {
+        fraction *= base;
+        scaled = scaled * base + fraction / one;
+        fraction %= one;
+    }
+    if fraction * 2 >= one {
+        scaled += 1;
+    }
+
+    whole.checked_add(scaled).ok_or(overflow)
+}
+
 pub fn run(args: &Args) {
-    let (converted, prefix) = Prefix::get(args.number);
-    println!("{converted}{prefix}");
+    if args.reverse {
+        let (negative, number) = match args.number.strip_prefix('-') {
+            Some(number) => (true, number),
+            None => (false, args.number.as_str()),
+        };
+        match parse_prefixed(number) {
+            Ok(n) if negative && n != 0 => println!("-{n}"),
+            Ok(n) => println!("{n}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.precision > MAX_PRECISION {
+        eprintln!("precision should be at most {MAX_PRECISION}");
+        std::process::exit(1);
+    }
+    // unsigned first, so the positive side keeps the whole u128 range
+    if let Ok(number) = args.number.parse::<u128>() {
+        println!("{}", humanize(number, args.si, args.precision));
+    } else if let Ok(number) = args.number.parse::<i128>() {
+        println!("{}", humanize_signed(number, args.si, args.precision));
+    } else {
+        eprintln!("number should be an integer");
+        std::process::exit(1);
+    }
+}
+
+/// the biggest remainder is just under 2^100, which leaves room for
+/// scaling it up by 10^8 without overflowing
+const MAX_PRECISION: u32 = 8;
+
+/// show a number with a prefix, truncating to the given number of
+/// decimal places
+fn humanize(number: u128, si: bool, precision: u32) -> String {
+    let (divisor, prefix) = if si {
+        Prefix::get_si(number)
+    } else {
+        Prefix::get(number)
+    };
+    let converted = number / divisor;
+    let fraction = number % divisor * 10_u128.pow(precision) / divisor;
+    let fraction = format!("{fraction:0width$}", width = precision as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{converted}{prefix}")
+    } else {
+        format!("{converted}.{fraction}{prefix}")
+    }
+}
+
+/// like [`humanize`], but with a leading - for negative numbers
+fn humanize_signed(number: i128, si: bool, precision: u32) -> String {
+    let humanized = humanize(number.unsigned_abs(), si, precision);
+    if number < 0 {
+        format!("-{humanized}")
+    } else {
+        humanized
+    }
+}
+
+#[test]
+fn prefixes() {
+    assert_eq!(humanize(1000, true, 0), "1k");
+    assert_eq!(humanize(1024, false, 0), "1Ki");
+    assert_eq!(humanize(999, true, 0), "999");
+    assert_eq!(humanize(u128::MAX, true, 0), "340282366Q");
+    assert_eq!(parse_prefixed("1.5k"), Ok(1500));
+    assert_eq!(parse_prefixed("1.5Ki"), Ok(1536));
+}
+
+#[test]
+fn precision() {
+    assert_eq!(humanize(1536, false, 0), "1Ki");
+    assert_eq!(humanize(1536, false, 1), "1.5Ki");
+    assert_eq!(humanize(1536, false, 2), "1.5Ki");
+    assert_eq!(humanize(1024, false, 2), "1Ki");
+    assert_eq!(humanize(1999, true, 2), "1.99k");
+    assert_eq!(
+        humanize(u128::MAX, false, MAX_PRECISION),
+        "268435455.99999999Qi"
+    );
+}
+
+#[test]
+fn signed() {
+    assert_eq!(humanize_signed(-1024, false, 0), "-1Ki");
+    assert_eq!(humanize_signed(-1500, true, 1), "-1.5k");
+    assert_eq!(humanize_signed(-1, false, 0), "-1");
+    assert_eq!(humanize_signed(0, false, 0), "0");
+    assert_eq!(humanize_signed(1024, false, 0), "1Ki");
+    assert_eq!(humanize_signed(i128::MIN, false, 0), "-134217728Qi");
 }
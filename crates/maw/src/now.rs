@@ -13,6 +13,13 @@ use std::time::{SystemTime, UNIX_EPOCH};
 #[argh(subcommand, name = "now")]
 #[argh(help_triggers("-h", "--help", "help"))]
 pub struct Args {
+    /// bits in each half of the feistel network, the time has to fit in
+    /// twice as many
+    #[argh(option, default = "BITS")]
+    bits: u8,
+    /// sorted characters to encode with, must have a power of two length
+    #[argh(option, default = "ALPHABET.to_string()")]
+    alphabet: String,
     #[argh(positional)]
     seed: u64,
     #[argh(subcommand)]
@@ -26,6 +33,7 @@ enum Action {
     Encode(EncodeAction),
     Convert(ConvertAction),
     Decode(DecodeAction),
+    Verify(VerifyAction),
 }
 
 /// format the current time
@@ -43,8 +51,17 @@ struct EncodeAction {
 #[argh(subcommand, name = "convert")]
 #[argh(help_triggers("-h", "--help"))]
 struct ConvertAction {
+    /// unit of time to round to
+    #[argh(option, default = "Accuracy::Second")]
+    accuracy: Accuracy,
+    /// take the time as seconds since the unix epoch
+    #[argh(switch)]
+    unix: bool,
+    /// also print the encrypted number in hex
+    #[argh(switch)]
+    hex: bool,
     #[argh(positional)]
-    timestamp: DateTime<Utc>,
+    timestamp: String,
 }
 
 /// decode the time to a normal format
@@ -52,36 +69,85 @@ struct ConvertAction {
 #[argh(subcommand, name = "decode")]
 #[argh(help_triggers("-h", "--help"))]
 struct DecodeAction {
+    /// accuracy the time was encoded with, only sub-second accuracies
+    /// decode differently
+    #[argh(option, default = "Accuracy::Second")]
+    accuracy: Accuracy,
     #[argh(positional)]
     blob: String,
 }
 
+/// check whether a time encodes to a blob
+#[derive(Clone, Debug, argh::FromArgs)]
+#[argh(subcommand, name = "verify")]
+#[argh(help_triggers("-h", "--help"))]
+struct VerifyAction {
+    /// unit of time the blob was rounded to
+    #[argh(option, default = "Accuracy::Second")]
+    accuracy: Accuracy,
+    /// take the time as seconds since the unix epoch
+    #[argh(switch)]
+    unix: bool,
+    /// another seed to try if the main one does not match, may be repeated
+    #[argh(option)]
+    candidate: Vec<u64>,
+    #[argh(positional)]
+    timestamp: String,
+    #[argh(positional)]
+    blob: String,
+}
+
+/// nanoseconds in each unit of time
 #[derive(Clone, Copy, Debug)]
+#[repr(u64)]
 enum Accuracy {
-    Second = 1,
-    Minute = 60,
-    Hour = 3600,
-    Day = 86400,
-    Week = 604800,
+    Nanosecond = 1,
+    Millisecond = 1_000_000,
+    Second = 1_000_000_000,
+    Minute = 60_000_000_000,
+    Hour = 3_600_000_000_000,
+    Day = 86_400_000_000_000,
+    Week = 604_800_000_000_000,
+}
+
+impl Accuracy {
+    /// nanoseconds in each unit of the encoded number
+    fn unit(self) -> u128 {
+        match self {
+            Self::Nanosecond => 1,
+            Self::Millisecond => 1_000_000,
+            _ => 1_000_000_000,
+        }
+    }
+
+    /// round nanoseconds since epoch into the encoded number
+    fn round(self, nanos: u128) -> u128 {
+        let step = self as u128;
+        nanos / step * step / self.unit()
+    }
 }
 
 impl std::str::FromStr for Accuracy {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
+            "nanosecond" | "ns" => Self::Nanosecond,
+            "millisecond" | "ms" => Self::Millisecond,
             "second" | "s" => Self::Second,
             "minute" | "m" => Self::Minute,
             "hour" | "h" => Self::Hour,
             "day" | "d" => Self::Day,
             "week" | "w" => Self::Week,
             _ => {
-                return Err("accuracy should be second, minute, hour, day, or week");
+                return Err(
+                    "accuracy should be nanosecond, millisecond, second, minute, hour, day, or week",
+                );
             }
         })
     }
 }
 
-const ALPHABET: &[u8] = b"[\\]^_abcdefghijklmnopqrstuvwxyz|";
+const ALPHABET: &str = "[\\]^_abcdefghijklmnopqrstuvwxyz|";
 const ROUNDS: &[u64] = &[
     11549304151469118491,
     10491820271401555799,
@@ -91,99 +157,335 @@ const ROUNDS: &[u64] = &[
     9559873045211917873,
 ];
 const BITS: u8 = 40;
-const MASK: u64 = (1 << BITS) - 1;
 
 fn round_func(inp: u64, seed: u64, round: u64) -> u64 {
     inp.overflowing_mul(round).0 ^ seed
 }
 
-fn fe(inp: u128, seed: u64) -> u128 {
-    let mut left = (inp >> BITS) as u64;
-    let mut right = inp as u64 & MASK;
+struct Format<'a> {
+    bits: u8,
+    mask: u64,
+    alphabet: &'a [u8],
+    /// bits per character
+    radix: u32,
+}
 
-    for round in ROUNDS {
-        left ^= round_func(right, seed, *round) & MASK;
-        (left, right) = (right, left);
+impl<'a> Format<'a> {
+    fn new(bits: u8, alphabet: &'a str) -> Result<Self, &'static str> {
+        if !(1..=64).contains(&bits) {
+            return Err("bits should be between 1 and 64");
+        }
+        let alphabet = alphabet.as_bytes();
+        if alphabet.len() < 2 || !alphabet.len().is_power_of_two() {
+            return Err("alphabet length should be a power of two");
+        }
+        if !alphabet.is_ascii() || !alphabet.is_sorted_by(|a, b| a < b) {
+            return Err("alphabet should be sorted ascii without duplicates");
+        }
+        Ok(Self {
+            bits,
+            mask: u64::MAX >> (64 - bits),
+            alphabet,
+            radix: alphabet.len().trailing_zeros(),
+        })
     }
 
-    (u128::from(left) << BITS) + u128::from(right)
-}
+    /// whether a number can go through the feistel network unscathed
+    fn fits(&self, inp: u128) -> bool {
+        inp.checked_shr(2 * u32::from(self.bits)).unwrap_or(0) == 0
+    }
 
-fn unfe(inp: u128, seed: u64) -> u128 {
-    let mut left = (inp >> BITS) as u64;
-    let mut right = inp as u64 & MASK;
+    fn fe(&self, inp: u128, seed: u64) -> u128 {
+        let mut left = (inp >> self.bits) as u64;
+        let mut right = inp as u64 & self.mask;
 
-    for round in ROUNDS.iter().rev() {
-        right ^= round_func(left, seed, *round) & MASK;
-        (left, right) = (right, left);
+        for round in ROUNDS {
+            left ^= round_func(right, seed, *round) & self.mask;
+            (left, right) = (right, left);
+        }
+
+        (u128::from(left) << self.bits) + u128::from(right)
     }
 
-    (u128::from(left) << BITS) + u128::from(right)
-}
+    fn unfe(&self, inp: u128, seed: u64) -> u128 {
+        let mut left = (inp >> self.bits) as u64;
+        let mut right = inp as u64 & self.mask;
 
-fn b32(mut inp: u128) -> String {
-    let mut out = String::new();
-    out.push(ALPHABET[(inp & 31) as usize] as char);
-    inp >>= 5;
+        for round in ROUNDS.iter().rev() {
+            right ^= round_func(left, seed, *round) & self.mask;
+            (left, right) = (right, left);
+        }
 
-    while inp > 0 {
-        out.push(ALPHABET[(inp & 31) as usize] as char);
-        inp >>= 5;
+        (u128::from(left) << self.bits) + u128::from(right)
     }
 
-    out
-}
+    fn b32(&self, mut inp: u128) -> String {
+        let digit = (1 << self.radix) - 1;
+        let mut out = String::new();
+        out.push(self.alphabet[(inp & digit) as usize] as char);
+        inp >>= self.radix;
 
-fn unb32(inp: &str) -> Option<u128> {
-    let mut out: u128 = 0;
+        while inp > 0 {
+            out.push(self.alphabet[(inp & digit) as usize] as char);
+            inp >>= self.radix;
+        }
 
-    for c in inp.chars().rev() {
-        out <<= 5;
-        out += ALPHABET.binary_search(&(c as u8)).ok()? as u128;
+        out
     }
 
-    Some(out)
+    fn unb32(&self, inp: &str) -> Result<u128, &'static str> {
+        let mut out: u128 = 0;
+
+        for c in inp.chars().rev() {
+            // checked_shl only fails for big shifts, not lost bits
+            if out >> (128 - self.radix) != 0 {
+                return Err("too long");
+            }
+            let digit = u8::try_from(c)
+                .ok()
+                .and_then(|c| self.alphabet.binary_search(&c).ok())
+                .ok_or("not in alphabet")?;
+            out = (out << self.radix) + digit as u128;
+        }
+
+        Ok(out)
+    }
 }
 
 pub fn run(args: &Args) {
+    let format = match Format::new(args.bits, &args.alphabet) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
     match &args.action {
         Action::Encode(EncodeAction { accuracy }) => {
-            let unix = SystemTime::now()
+            let nanos = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
-                .as_secs();
-            let unix = unix / *accuracy as u64 * *accuracy as u64;
-            let ob = fe(unix.into(), args.seed);
-            println!("{}", b32(ob));
+                .as_nanos();
+            encode(&format, accuracy.round(nanos), args.seed, false);
         }
-        Action::Convert(ConvertAction { timestamp }) => {
-            let unix = timestamp.timestamp();
-            #[allow(clippy::cast_sign_loss)]
-            let ob = fe(u128::from(unix as u64), args.seed);
-            println!("{}", b32(ob));
+        Action::Convert(ConvertAction {
+            accuracy,
+            unix,
+            hex,
+            timestamp,
+        }) => match parse_time(timestamp, *unix) {
+            Ok(nanos) => encode(&format, accuracy.round(nanos), args.seed, *hex),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
+        Action::Decode(DecodeAction { accuracy, blob }) => {
+            match decode(&format, blob, args.seed, *accuracy) {
+                Ok(time) => println!("{time}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
         }
-        Action::Decode(DecodeAction { blob }) => {
-            let ob = unb32(blob).expect("not in alphabet");
-            let unix: u64 = unfe(ob, args.seed).try_into().expect("not a time");
-            #[allow(clippy::cast_possible_wrap)]
-            let time = DateTime::from_timestamp(unix as i64, 0).unwrap();
-            println!("{time}");
+        Action::Verify(VerifyAction {
+            accuracy,
+            unix,
+            candidate,
+            timestamp,
+            blob,
+        }) => {
+            let seeds: Vec<u64> = std::iter::once(args.seed)
+                .chain(candidate.iter().copied())
+                .collect();
+            let found = parse_time(timestamp, *unix)
+                .and_then(|nanos| verify(&format, accuracy.round(nanos), blob, &seeds));
+            match found {
+                Ok(Some(seed)) => println!("matches with seed {seed}"),
+                Ok(None) => {
+                    eprintln!("no seed matches");
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
         }
     }
 }
 
+/// nanoseconds since epoch from either a date or unix timestamp
+fn parse_time(timestamp: &str, unix: bool) -> Result<u128, &'static str> {
+    let (secs, subsec) = if unix {
+        let secs: i64 = timestamp.parse().map_err(|_| "not a unix timestamp")?;
+        (secs, 0)
+    } else {
+        let time: DateTime<Utc> = timestamp
+            .parse()
+            .map_err(|_| "time should look like 2025-10-19T12:34:56Z")?;
+        (time.timestamp(), time.timestamp_subsec_nanos())
+    };
+    let secs = u128::try_from(secs).map_err(|_| "time is before the epoch")?;
+    Ok(secs * 1_000_000_000 + u128::from(subsec))
+}
+
+fn encode(format: &Format, unix: u128, seed: u64, hex: bool) {
+    if !format.fits(unix) {
+        eprintln!("time does not fit in {} bits", 2 * format.bits);
+        std::process::exit(1);
+    }
+    let encrypted = format.fe(unix, seed);
+    if hex {
+        println!("{} {encrypted:x}", format.b32(encrypted));
+    } else {
+        println!("{}", format.b32(encrypted));
+    }
+}
+
+fn decode(
+    format: &Format,
+    blob: &str,
+    seed: u64,
+    accuracy: Accuracy,
+) -> Result<DateTime<Utc>, &'static str> {
+    let ob = format.unb32(blob)?;
+    if !format.fits(ob) {
+        return Err("not a time");
+    }
+    let nanos = format
+        .unfe(ob, seed)
+        .checked_mul(accuracy.unit())
+        .ok_or("not a time")?;
+    let secs: i64 = (nanos / 1_000_000_000)
+        .try_into()
+        .map_err(|_| "not a time")?;
+    DateTime::from_timestamp(secs, (nanos % 1_000_000_000) as u32).ok_or("time out of range")
+}
+
+/// first of the seeds that encrypts the time into the blob
+fn verify(
+    format: &Format,
+    unix: u128,
+    blob: &str,
+    seeds: &[u64],
+) -> Result<Option<u64>, &'static str> {
+    if !format.fits(unix) {
+        return Err("time does not fit");
+    }
+    let ob = format.unb32(blob)?;
+    Ok(seeds
+        .iter()
+        .copied()
+        .find(|&seed| format.fe(unix, seed) == ob))
+}
+
 #[test]
 fn refe() {
+    let format = Format::new(BITS, ALPHABET).unwrap();
     for i in 0..100 {
-        let f = fe(i, 6);
-        assert_eq!(unfe(f, 6), i);
+        let f = format.fe(i, 6);
+        assert_eq!(format.unfe(f, 6), i);
     }
 }
 
+#[test]
+fn refe_bits() {
+    let format = Format::new(20, "0123456789abcdef").unwrap();
+    for i in 0..100 {
+        let f = format.fe(i, 6);
+        assert_eq!(format.unfe(f, 6), i);
+        assert_eq!(format.unb32(&format.b32(f)), Ok(f));
+    }
+}
+
+#[test]
+fn bad_format() {
+    assert!(Format::new(0, ALPHABET).is_err());
+    assert!(Format::new(65, ALPHABET).is_err());
+    assert!(Format::new(BITS, "abc").is_err());
+    assert!(Format::new(BITS, "ba").is_err());
+    assert!(Format::new(BITS, "aa").is_err());
+}
+
+#[test]
+fn bad_decode() {
+    let format = Format::new(BITS, ALPHABET).unwrap();
+    assert_eq!(
+        decode(&format, "A", 6, Accuracy::Second),
+        Err("not in alphabet")
+    );
+    assert_eq!(
+        decode(&format, &format.b32(u128::MAX >> 48), 6, Accuracy::Second),
+        Err("not a time")
+    );
+    // too long to fit in a u128 rather than wrapping around
+    let long = format!("{}\\", "[".repeat(25));
+    assert_eq!(format.unb32(&long), Ok(1 << 125));
+    let long = format!("{}\\", "[".repeat(26));
+    assert_eq!(format.unb32(&long), Err("too long"));
+    assert_eq!(decode(&format, &long, 6, Accuracy::Second), Err("too long"));
+}
+
+#[test]
+fn subsecond() {
+    let format = Format::new(BITS, ALPHABET).unwrap();
+    let nanos = 1_700_000_000_123_456_789;
+    for (accuracy, expected) in [
+        (Accuracy::Nanosecond, 123_456_789),
+        (Accuracy::Millisecond, 123_000_000),
+        (Accuracy::Second, 0),
+    ] {
+        let blob = format.b32(format.fe(accuracy.round(nanos), 6));
+        let time = decode(&format, &blob, 6, accuracy).unwrap();
+        assert_eq!(time.timestamp(), 1_700_000_000);
+        assert_eq!(time.timestamp_subsec_nanos(), expected);
+    }
+    assert_eq!(Accuracy::Minute.round(nanos), 1_699_999_980);
+}
+
+#[test]
+fn time_inputs() {
+    assert_eq!(
+        parse_time("1700000000", true),
+        Ok(1_700_000_000_000_000_000)
+    );
+    assert_eq!(
+        parse_time("2023-11-14T22:13:20.5Z", false),
+        Ok(1_700_000_000_500_000_000)
+    );
+    assert_eq!(parse_time("-1", true), Err("time is before the epoch"));
+    assert_eq!(parse_time("soon", true), Err("not a unix timestamp"));
+    assert!(parse_time("1700000000", false).is_err());
+}
+
 #[test]
 fn reb32() {
+    let format = Format::new(BITS, ALPHABET).unwrap();
     for i in 0..100 {
-        let s = b32(i);
-        assert_eq!(unb32(&s), Some(i));
+        let s = format.b32(i);
+        assert_eq!(format.unb32(&s), Ok(i));
     }
 }
+
+#[test]
+fn verify_seeds() {
+    let format = Format::new(BITS, ALPHABET).unwrap();
+    let blob = format.b32(format.fe(1_700_000_000, 6));
+    assert_eq!(verify(&format, 1_700_000_000, &blob, &[6]), Ok(Some(6)));
+    assert_eq!(
+        verify(&format, 1_700_000_000, &blob, &[5, 6, 7]),
+        Ok(Some(6))
+    );
+    assert_eq!(verify(&format, 1_700_000_001, &blob, &[6]), Ok(None));
+    assert_eq!(verify(&format, 1_700_000_000, &blob, &[]), Ok(None));
+    assert_eq!(
+        verify(&format, 1_700_000_000, "A", &[6]),
+        Err("not in alphabet")
+    );
+    assert_eq!(
+        verify(&format, u128::MAX, &blob, &[6]),
+        Err("time does not fit")
+    );
+}
@@ -2,20 +2,78 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{cmp::Ord, convert::From, fmt, fs::read_to_string, path::PathBuf};
+use std::{
+    cmp::Ord,
+    collections::HashSet,
+    fmt,
+    fs::{File, read_to_string},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
 use url::Url;
 
 /// sort urls in domain order
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand, name = "sort")]
 #[argh(help_triggers("-h", "--help"))]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Args {
+    /// sort in reverse order
+    #[argh(switch, short = 'r')]
+    reverse: bool,
+    /// drop duplicate lines
+    #[argh(switch, short = 'u')]
+    unique: bool,
+    /// put a blank line between urls from different sites
+    #[argh(switch, short = 'g')]
+    group: bool,
+    /// ignore case when comparing domains (the default)
+    #[argh(switch)]
+    fold_case: bool,
+    /// compare domains case-sensitively, as they were written
+    #[argh(switch)]
+    no_fold_case: bool,
+    /// write to a file instead of stdout
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+    /// files to sort, or - for stdin (the default)
     #[argh(positional, greedy)]
     files: Vec<PathBuf>,
 }
 
+/// split a domain into its labels, tld first, decoding punycode so
+/// internationalized domains sort by what they look like
+fn domain_parts(host: &str, fold_case: bool) -> Vec<String> {
+    if !fold_case {
+        return host
+            .rsplit('.')
+            .map(|label| {
+                (label.get(..4))
+                    .filter(|p| p.eq_ignore_ascii_case("xn--"))
+                    .and_then(|_| idna::punycode::decode_to_string(&label[4..]))
+                    .unwrap_or_else(|| label.to_string())
+            })
+            .collect();
+    }
+    let (unicode, res) = idna::domain_to_unicode(host);
+    let host = if res.is_ok() {
+        unicode
+    } else {
+        host.to_ascii_lowercase()
+    };
+    host.rsplit('.').map(str::to_string).collect()
+}
+
+/// find the host as it was written, since parsing a url lowercases it
+fn raw_host<'a>(raw: &'a str, host: &str) -> Option<&'a str> {
+    let after_scheme = raw.find("://").map_or(0, |i| i + 3);
+    let start = after_scheme + raw[after_scheme..].to_ascii_lowercase().find(host)?;
+    raw.get(start..start + host.len())
+}
+
 #[derive(Debug, Eq, PartialEq)]
 struct ParsedUrl {
+    raw: String,
     url: Url,
     domain_parts: Vec<String>,
 }
@@ -29,6 +87,7 @@ impl Ord for ParsedUrl {
             .then(self.url.query().cmp(&other.url.query()))
             .then(self.url.fragment().cmp(&other.url.fragment()))
             .then(self.url.cmp(&other.url))
+            .then(self.raw.cmp(&other.raw))
     }
 }
 
@@ -59,19 +118,32 @@ impl PartialOrd for BareDomain {
 #[derive(Debug, Eq, PartialEq)]
 struct InfailableUrl(Result<ParsedUrl, BareDomain>);
 
-impl From<String> for InfailableUrl {
-    fn from(inp: String) -> Self {
+impl InfailableUrl {
+    fn new(inp: String, fold_case: bool) -> Self {
         let parsed = if let Ok(url) = Url::parse(&inp) {
-            let domain_parts = if let Some(host) = url.domain() {
-                host.rsplit('.').map(str::to_ascii_lowercase).collect()
-            } else if let Some(host) = url.host_str() {
-                vec![host.to_ascii_lowercase()]
+            let host = url.host_str().map(|host| {
+                if fold_case {
+                    host
+                } else {
+                    raw_host(&inp, host).unwrap_or(host)
+                }
+            });
+            let domain_parts = if url.domain().is_some()
+                && let Some(host) = host
+            {
+                domain_parts(host, fold_case)
+            } else if let Some(host) = host {
+                vec![host.to_string()]
             } else {
                 vec![]
             };
-            Ok(ParsedUrl { url, domain_parts })
+            Ok(ParsedUrl {
+                raw: inp,
+                url,
+                domain_parts,
+            })
         } else {
-            let domain_parts = inp.rsplit('.').map(str::to_ascii_lowercase).collect();
+            let domain_parts = domain_parts(&inp, fold_case);
             Err(BareDomain {
                 raw: inp,
                 domain_parts,
@@ -79,11 +151,47 @@ impl From<String> for InfailableUrl {
         };
         Self(parsed)
     }
+
+    fn domain_parts(&self) -> &[String] {
+        match &self.0 {
+            Ok(p) => &p.domain_parts,
+            Err(s) => &s.domain_parts,
+        }
+    }
+
+    /// a rough guess at the registrable domain, without lugging around
+    /// the whole public suffix list. this is usually the last two
+    /// labels, or three for things like co.uk
+    fn site(&self) -> &[String] {
+        let parts = self.domain_parts();
+        let len = match parts {
+            [tld, sld, _, ..]
+                if tld.len() == 2
+                    && matches!(
+                        sld.as_str(),
+                        "ac" | "co" | "com" | "edu" | "gov" | "net" | "org"
+                    ) =>
+            {
+                3
+            }
+            _ => 2,
+        };
+        &parts[..parts.len().min(len)]
+    }
 }
 
 impl Ord for InfailableUrl {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
+        match (&self.0, &other.0) {
+            (Ok(a), Ok(b)) => a.cmp(b),
+            (Err(a), Err(b)) => a.cmp(b),
+            // mix bare domains in with the urls, going first when the
+            // domain is the same
+            (a, b) => self
+                .domain_parts()
+                .cmp(other.domain_parts())
+                .then(a.is_ok().cmp(&b.is_ok())),
+        }
     }
 }
 
@@ -96,26 +204,195 @@ impl PartialOrd for InfailableUrl {
 impl fmt::Display for InfailableUrl {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.0 {
-            Ok(p) => p.url.fmt(f),
-            Err(s) => s.raw.fmt(f),
+            Ok(ParsedUrl { raw, .. }) | Err(BareDomain { raw, .. }) => raw.fmt(f),
         }
     }
 }
 
+fn read_input(name: &Path) -> std::io::Result<String> {
+    if name.as_os_str() == "-" {
+        return std::io::read_to_string(std::io::stdin());
+    }
+    read_to_string(name)
+}
+
 pub fn run(args: &Args) {
-    let mut urls = Vec::new();
+    if args.fold_case && args.no_fold_case {
+        eprintln!("--fold-case and --no-fold-case cannot be used together");
+        std::process::exit(1);
+    }
+    let mut lines = Vec::new();
     let files = if args.files.is_empty() {
-        &vec![PathBuf::from("/dev/stdin")]
+        &vec![PathBuf::from("-")]
     } else {
         &args.files
     };
     for name in files {
-        for line in read_to_string(name).unwrap().lines() {
-            urls.push(InfailableUrl::from(line.to_string()));
-        }
+        lines.extend(read_input(name).unwrap().lines().map(str::to_string));
+    }
+    if args.unique {
+        // compare the lines themselves, since different urls can parse
+        // to the same thing
+        let mut seen = HashSet::new();
+        lines.retain(|line| seen.insert(line.clone()));
+    }
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("could not open {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+    if let Err(e) = sort(lines, args, &mut out).and_then(|()| out.flush()) {
+        eprintln!("could not write: {e}");
+        std::process::exit(1);
     }
+}
+
+/// sort the lines as urls and write them out
+fn sort(lines: Vec<String>, args: &Args, out: &mut impl Write) -> std::io::Result<()> {
+    let mut urls: Vec<_> = lines
+        .into_iter()
+        .map(|line| InfailableUrl::new(line, !args.no_fold_case))
+        .collect();
     urls.sort();
-    for url in urls {
-        println!("{url}");
+    if args.reverse {
+        urls.reverse();
     }
+
+    let mut prev_site = None;
+    for url in &urls {
+        if args.group {
+            let site = url.site();
+            if prev_site.is_some_and(|p| p != site) {
+                writeln!(out)?;
+            }
+            prev_site = Some(site);
+        }
+        writeln!(out, "{url}")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn idn_parts() {
+    assert_eq!(
+        domain_parts("xn--bcher-kva.Example", true),
+        ["example", "bücher"]
+    );
+    assert_eq!(domain_parts("Bücher.example", true), ["example", "bücher"]);
+    assert_eq!(domain_parts("not a domain", true), ["not a domain"]);
+
+    assert_eq!(
+        domain_parts("XN--bcher-kva.Example", false),
+        ["Example", "bücher"]
+    );
+    assert_eq!(domain_parts("Bücher.example", false), ["example", "Bücher"]);
+}
+
+#[test]
+fn case_folding() {
+    let sorted = |fold_case| {
+        let mut urls: Vec<_> = ["https://b.example/", "A.example", "https://C.example/"]
+            .map(|u| InfailableUrl::new(u.to_string(), fold_case))
+            .into_iter()
+            .collect();
+        urls.sort();
+        urls.iter().map(ToString::to_string).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sorted(true),
+        ["A.example", "https://b.example/", "https://C.example/"]
+    );
+    assert_eq!(
+        sorted(false),
+        ["A.example", "https://C.example/", "https://b.example/"]
+    );
+}
+
+#[cfg(test)]
+fn sort_with(flags: &[&str], input: &str) -> String {
+    use argh::FromArgs;
+
+    let args = Args::from_args(&["sort"], flags).unwrap();
+    let mut out = vec![];
+    sort(input.lines().map(str::to_string).collect(), &args, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn reverse() {
+    let input = "https://b.example/\na.example\nhttps://c.example/x\nhttps://c.example/\n";
+    assert_eq!(
+        sort_with(&[], input),
+        "a.example\nhttps://b.example/\nhttps://c.example/\nhttps://c.example/x\n"
+    );
+    assert_eq!(
+        sort_with(&["--reverse"], input),
+        "https://c.example/x\nhttps://c.example/\nhttps://b.example/\na.example\n"
+    );
+}
+
+#[test]
+fn unique_and_output() {
+    use argh::FromArgs;
+
+    let dir = std::env::temp_dir().join(format!("maw-sort-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("in.txt");
+    // the first two are the same url written differently, so both stay
+    std::fs::write(
+        &input,
+        "https://b.example/\nhttps://B.example/\na.example\nhttps://b.example/\n",
+    )
+    .unwrap();
+    let output = dir.join("out.txt");
+    let path = |p: &Path| p.to_str().unwrap().to_string();
+
+    let args = Args::from_args(&["sort"], &["--output", &path(&output), &path(&input)]).unwrap();
+    run(&args);
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "a.example\nhttps://B.example/\nhttps://b.example/\nhttps://b.example/\n"
+    );
+
+    let args = Args::from_args(
+        &["sort"],
+        &["--unique", "--output", &path(&output), &path(&input)],
+    )
+    .unwrap();
+    run(&args);
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "a.example\nhttps://B.example/\nhttps://b.example/\n"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sites() {
+    let site = |u: &str| InfailableUrl::new(u.to_string(), true).site().join(".");
+    assert_eq!(site("https://www.example.co.uk/"), "uk.co.example");
+    assert_eq!(site("https://a.b.example.com/"), "com.example");
+    assert_eq!(site("example.org"), "org.example");
+    assert_eq!(site("https://co.uk/"), "uk.co");
+    assert_eq!(site("localhost"), "localhost");
+    assert_eq!(site("https://192.0.2.1/"), "192.0.2.1");
+}
+
+#[test]
+fn group() {
+    let input = "https://www.example.co.uk/\nexample.com\nhttps://b.example.com/x\n\
+                 https://a.example.co.uk/\nhttps://other.co.uk/\nhttps://example.net/\n";
+    assert_eq!(
+        sort_with(&["--group"], input),
+        "example.com\nhttps://b.example.com/x\n\n\
+         https://example.net/\n\n\
+         https://a.example.co.uk/\nhttps://www.example.co.uk/\n\n\
+         https://other.co.uk/\n"
+    );
 }
@@ -2,16 +2,35 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
 use unicode_names2::{character, name};
+use unicode_segmentation::UnicodeSegmentation;
 
 /// decode unicode characters
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand, name = "uni")]
 #[argh(help_triggers("-h", "--help"))]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Args {
     /// reverse mode
     #[argh(switch, short = 'r')]
     reverse: bool,
+    /// find characters with names containing this
+    #[argh(option, short = 's')]
+    search: Option<String>,
+    /// how many search results to show at most
+    #[argh(option, default = "100")]
+    limit: usize,
+    /// describe each grapheme cluster on its own line, instead of
+    /// every codepoint together
+    #[argh(switch, short = 'g')]
+    graphemes: bool,
+    /// normalize to nfc first
+    #[argh(switch)]
+    nfc: bool,
+    /// normalize to nfd first
+    #[argh(switch)]
+    nfd: bool,
     #[argh(positional, greedy)]
     input: Vec<String>,
 }
@@ -25,7 +44,57 @@ fn fmt_output(c: char) -> String {
     )
 }
 
+/// get the codepoint out of things like U+2603, or bare hex with at
+/// least 4 digits so short words still get described as text
+fn parse_codepoint(arg: &str) -> Option<u32> {
+    let hex = match arg.strip_prefix("U+").or_else(|| arg.strip_prefix("u+")) {
+        Some(hex) => hex,
+        None if arg.len() >= 4 => arg,
+        None => return None,
+    };
+    if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    // too many digits is as out of range as a big number
+    Some(u32::from_str_radix(hex, 16).unwrap_or(u32::MAX))
+}
+
+/// normalize to nfc or nfd, or leave the text alone if neither
+fn normalize(text: String, nfc: bool, nfd: bool) -> String {
+    if nfc {
+        ComposingNormalizerBorrowed::new_nfc()
+            .normalize(&text)
+            .into_owned()
+    } else if nfd {
+        DecomposingNormalizerBorrowed::new_nfd()
+            .normalize(&text)
+            .into_owned()
+    } else {
+        text
+    }
+}
+
+fn search(query: &str, limit: usize) {
+    let query = query.to_ascii_uppercase();
+    // unicode_names2 cannot iterate over its names, so go through
+    // every codepoint instead
+    let mut found = (0..=char::MAX as u32)
+        .filter_map(char::from_u32)
+        .filter(|&c| name(c).is_some_and(|n| n.to_string().contains(&query)));
+    for c in found.by_ref().take(limit) {
+        println!("{}", fmt_output(c));
+    }
+    let rest = found.count();
+    if rest > 0 {
+        eprintln!("{rest} more not shown, use --limit to see them");
+    }
+}
+
 pub fn run(args: &Args) {
+    if let Some(query) = &args.search {
+        search(query, args.limit);
+        return;
+    }
     if args.reverse {
         for arg in &args.input {
             print!("{}", character(arg).unwrap_or('\u{fffd}'));
@@ -34,9 +103,42 @@ pub fn run(args: &Args) {
         return;
     }
 
+    if args.nfc && args.nfd {
+        eprintln!("only one of --nfc and --nfd can be given");
+        std::process::exit(1);
+    }
+
     for arg in &args.input {
-        let chars = arg.chars();
-        let out: Vec<_> = chars.map(fmt_output).collect();
+        let text = if let Some(codepoint) = parse_codepoint(arg) {
+            let Some(c) = char::from_u32(codepoint) else {
+                println!("{arg} invalid codepoint");
+                continue;
+            };
+            c.to_string()
+        } else {
+            arg.clone()
+        };
+        let text = normalize(text, args.nfc, args.nfd);
+        if args.graphemes {
+            for cluster in text.graphemes(true) {
+                let out: Vec<_> = cluster.chars().map(fmt_output).collect();
+                println!("{cluster}: {}", out.join(" + "));
+            }
+            continue;
+        }
+        let out: Vec<_> = text.chars().map(fmt_output).collect();
         println!("{}", out.join(" "));
     }
 }
+
+#[test]
+fn normalization() {
+    assert_eq!(normalize("e\u{301}".to_string(), true, false), "\u{e9}");
+    assert_eq!(normalize("\u{e9}".to_string(), false, true), "e\u{301}");
+    assert_eq!(normalize("e\u{301}".to_string(), false, false), "e\u{301}");
+    // hangul syllables compose from their jamo
+    assert_eq!(
+        normalize("\u{1100}\u{1161}\u{11a8}".to_string(), true, false),
+        "\u{ac01}"
+    );
+}
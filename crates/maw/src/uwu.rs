@@ -3,8 +3,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use std::{
-    fs::{read, read_to_string},
-    io::{Write, stdout},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write, stdout},
     path::PathBuf,
 };
 
@@ -16,6 +16,9 @@ pub struct Args {
     /// the action to do (encode or decode)
     #[argh(positional)]
     action: Action,
+    /// when decoding, fail on unknown words or a missing last nibble
+    #[argh(switch)]
+    strict: bool,
     #[argh(positional, greedy)]
     files: Vec<PathBuf>,
 }
@@ -53,13 +56,88 @@ fn unuwu_nibble(word: &str) -> Option<u8> {
     UWUS.iter().position(|i| *i == word).map(|n| n as u8)
 }
 
-fn unuwu_string(s: &str) -> Vec<u8> {
-    s.split_ascii_whitespace()
-        .filter_map(unuwu_nibble)
-        .collect::<Vec<_>>()
-        .chunks(2)
-        .map(|c| (c[0] << 4) + c.get(1).unwrap_or(&0))
-        .collect()
+fn unuwu_word(word: &[u8]) -> Option<u8> {
+    str::from_utf8(word).ok().and_then(unuwu_nibble)
+}
+
+fn encode(mut read: impl Read, out: &mut impl Write) -> io::Result<()> {
+    let mut buf = [0; 8192];
+    let mut first = true;
+    loop {
+        let len = match read.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for word in buf[..len].iter().flat_map(|&b| uwu_byte(b)) {
+            if !first {
+                out.write_all(b" ")?;
+            }
+            first = false;
+            out.write_all(word.as_bytes())?;
+        }
+    }
+    out.write_all(b"\n")
+}
+
+fn decode(mut read: impl BufRead, out: &mut impl Write, strict: bool) -> io::Result<()> {
+    let mut word = Vec::new();
+    let mut high = None;
+    let mut position = 0;
+    let mut push = |word: &mut Vec<u8>, out: &mut dyn Write| -> io::Result<()> {
+        if word.is_empty() {
+            return Ok(());
+        }
+        position += 1;
+        match unuwu_word(word) {
+            Some(nibble) => {
+                if let Some(h) = high.take() {
+                    out.write_all(&[(h << 4) + nibble])?;
+                } else {
+                    high = Some(nibble);
+                }
+            }
+            None if strict => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "unknown word {:?} at position {position}",
+                        String::from_utf8_lossy(word)
+                    ),
+                ));
+            }
+            None => (),
+        }
+        word.clear();
+        Ok(())
+    };
+    loop {
+        let buf = read.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len();
+        for &b in buf {
+            if b.is_ascii_whitespace() {
+                push(&mut word, out)?;
+            } else {
+                word.push(b);
+            }
+        }
+        read.consume(len);
+    }
+    push(&mut word, out)?;
+    if let Some(h) = high {
+        if strict {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "odd number of words, the last byte is missing a nibble",
+            ));
+        }
+        out.write_all(&[h << 4])?;
+    }
+    Ok(())
 }
 
 pub fn run(args: &Args) {
@@ -68,25 +146,103 @@ pub fn run(args: &Args) {
     } else {
         &args.files
     };
-    match &args.action {
-        Action::Encode => {
-            for name in files {
-                println!(
-                    "{}",
-                    read(name)
-                        .unwrap()
-                        .into_iter()
-                        .flat_map(uwu_byte)
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                );
-            }
-        }
-        Action::Decode => {
-            for name in files {
-                let s = read_to_string(name).unwrap();
-                stdout().write_all(&unuwu_string(&s)).unwrap();
-            }
+    let mut out = BufWriter::new(stdout().lock());
+    for name in files {
+        let res = File::open(name).and_then(|file| match &args.action {
+            Action::Encode => encode(file, &mut out),
+            Action::Decode => decode(BufReader::new(file), &mut out, args.strict),
+        });
+        if let Err(e) = res {
+            _ = out.flush();
+            eprintln!("{}: {e}", name.display());
+            std::process::exit(1);
         }
     }
+    out.flush().unwrap();
+}
+
+/// how encoding and decoding worked before streaming, to compare against
+#[cfg(test)]
+fn whole_file(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let encoded = format!(
+        "{}\n",
+        input
+            .iter()
+            .copied()
+            .flat_map(uwu_byte)
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let decoded = String::from_utf8_lossy(input)
+        .split_ascii_whitespace()
+        .filter_map(unuwu_nibble)
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .map(|c| (c[0] << 4) + c.get(1).unwrap_or(&0))
+        .collect();
+    (encoded.into_bytes(), decoded)
+}
+
+#[cfg(test)]
+fn streamed(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut encoded = vec![];
+    encode(input, &mut encoded).unwrap();
+    let mut decoded = vec![];
+    // a tiny buffer splits words across reads
+    decode(BufReader::with_capacity(5, input), &mut decoded, false).unwrap();
+    (encoded, decoded)
+}
+
+#[test]
+fn roundtrip() {
+    let input: Vec<u8> = (0..20000u32)
+        .map(|i| u8::try_from(i * 7 % 251).unwrap())
+        .collect();
+    let mut encoded = vec![];
+    encode(&input[..], &mut encoded).unwrap();
+    let mut decoded = vec![];
+    decode(BufReader::new(&encoded[..]), &mut decoded, true).unwrap();
+    assert_eq!(decoded, input);
+}
+
+#[test]
+fn same_as_before() {
+    let long: Vec<u8> = (0..=255).cycle().take(8193).collect();
+    for input in [
+        &b""[..],
+        b"meow",
+        &long,
+        // an odd number of nibbles gets padded
+        b"owo uwu nya",
+        b"owo\tuwu\r\nnya\x0cUwU  q_p\n",
+        b"owo meow uwu nyanya O_O",
+        b"  \n",
+    ] {
+        assert_eq!(streamed(input), whole_file(input), "{input:?}");
+    }
+    assert_eq!(streamed(b"owo uwu nya").1, [0x10, 0x30]);
+}
+
+#[test]
+fn strict() {
+    let strict_decode = |input: &[u8]| {
+        let mut out = vec![];
+        let res = decode(input, &mut out, true).map_err(|e| e.to_string());
+        (out, res)
+    };
+    assert_eq!(strict_decode(b"owo uwu\n"), (vec![0x10], Ok(())));
+    assert_eq!(
+        strict_decode(b"owo uwu nya meow owo"),
+        (
+            vec![0x10],
+            Err("unknown word \"meow\" at position 4".to_string())
+        )
+    );
+    assert_eq!(
+        strict_decode(b"owo uwu nya"),
+        (
+            vec![0x10],
+            Err("odd number of words, the last byte is missing a nibble".to_string())
+        )
+    );
 }
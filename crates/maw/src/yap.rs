@@ -25,6 +25,7 @@ pub struct Args {
 enum Actions {
     Generate(GenerateAction),
     Show(ShowAction),
+    Verify(VerifyAction),
 }
 
 /// generate a yap token
@@ -32,6 +33,12 @@ enum Actions {
 #[argh(subcommand, name = "generate")]
 #[argh(help_triggers("-h", "--help"))]
 struct GenerateAction {
+    /// print each token as a line of json
+    #[argh(switch)]
+    json: bool,
+    /// how many different tokens to generate
+    #[argh(option, default = "1")]
+    count: usize,
     #[argh(positional)]
     ip: IpAddr,
     #[argh(positional)]
@@ -45,10 +52,33 @@ struct GenerateAction {
 #[argh(subcommand, name = "show")]
 #[argh(help_triggers("-h", "--help"))]
 struct ShowAction {
+    /// how many seconds a token stays valid for
+    #[argh(option, default = "MAX_AGE")]
+    max_age: u64,
+    /// print the metadata as json
+    #[argh(switch)]
+    json: bool,
     #[argh(positional)]
     token: String,
 }
 
+/// check if a yap token is valid
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand, name = "verify")]
+#[argh(help_triggers("-h", "--help"))]
+struct VerifyAction {
+    /// how many seconds a token stays valid for
+    #[argh(option, default = "MAX_AGE")]
+    max_age: u64,
+    #[argh(positional)]
+    token: String,
+    #[argh(positional)]
+    difficulty: u8,
+}
+
+/// tokens are valid for a week by default
+const MAX_AGE: u64 = 604_800;
+
 fn unixtime() -> u64 {
     let duration = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -88,7 +118,26 @@ fn verify() {
     assert!(!check(80, [0; 24], 7));
 }
 
-fn generate(ip: &IpAddr, difficulty: u8, time: Option<u64>) {
+/// find `count` different nonces for a challenge, each search
+/// picking up after the last nonce found so none repeat
+fn find_nonces(challenge: [u8; 24], difficulty: u8, count: usize) -> Vec<u64> {
+    assert_eq!(Sha256::output_size(), 32);
+    let mut start = 0;
+    let mut nonces = Vec::with_capacity(count);
+    for _ in 0..count {
+        let Some(result) = (start..u64::MAX)
+            .into_par_iter()
+            .find_any(|n| check(*n, challenge, difficulty))
+        else {
+            break;
+        };
+        nonces.push(result);
+        start = result + 1;
+    }
+    nonces
+}
+
+fn generate(ip: &IpAddr, difficulty: u8, time: Option<u64>, count: usize, json: bool) {
     if difficulty > 128 {
         eprintln!("i cut corners so difficulty > 128 is not supported");
         return;
@@ -96,60 +145,205 @@ fn generate(ip: &IpAddr, difficulty: u8, time: Option<u64>) {
     let time = time.unwrap_or_else(unixtime);
     let challenge: [u8; 24] = gen_challenge(ip, time).try_into().unwrap();
 
-    assert_eq!(Sha256::output_size(), 32);
-    let result = (0..u64::MAX)
-        .into_par_iter()
-        .find_any(|n| check(*n, challenge, difficulty))
-        .unwrap();
+    for nonce in find_nonces(challenge, difficulty, count) {
+        let mut combined = nonce.to_be_bytes().to_vec();
+        combined.extend(challenge);
+        let encoded = BASE64_STANDARD.encode(&combined);
+        if json {
+            println!(r#"{{"token":"{encoded}","nonce":"{nonce:016x}","time":{time},"ip":"{ip}"}}"#);
+        } else {
+            println!("{encoded}");
+        }
+    }
+}
 
-    let mut combined = result.to_be_bytes().to_vec();
-    combined.extend(challenge);
-    let encoded = BASE64_STANDARD.encode(&combined);
-    println!("{encoded}");
+/// the metadata of a yap token
+struct Shown {
+    nonce: u64,
+    time: u64,
+    valid: bool,
+    ip: Ipv6Addr,
+    hash: [u128; 2],
+    zeros: u32,
 }
 
-fn show(token: &str) {
-    let Ok(decoded) = BASE64_STANDARD.decode(token) else {
-        eprintln!("could not decode base64");
-        return;
+impl Shown {
+    fn new(token: &str, max_age: u64) -> Result<Self, &'static str> {
+        let decoded = BASE64_STANDARD
+            .decode(token)
+            .map_err(|_| "could not decode base64")?;
+        let decoded: [u8; 32] = decoded.try_into().map_err(|_| "wrong length")?;
+        let nonce = u64::from_be_bytes(decoded[0..8].try_into().unwrap());
+        let time = u64::from_le_bytes(decoded[8..16].try_into().unwrap());
+        let valid = unixtime()
+            .checked_sub(time)
+            .is_some_and(|age| age <= max_age);
+        let ip = Ipv6Addr::from_bits(u128::from_le_bytes(decoded[16..32].try_into().unwrap()));
+        let hash = Sha256::digest(decoded);
+        let high = u128::from_be_bytes(hash[0..16].try_into().unwrap());
+        let low = u128::from_be_bytes(hash[16..32].try_into().unwrap());
+        let mut zeros = high.leading_zeros();
+        if zeros == 128 {
+            zeros += low.leading_zeros();
+        }
+        Ok(Self {
+            nonce,
+            time,
+            valid,
+            ip,
+            hash: [high, low],
+            zeros,
+        })
+    }
+
+    fn json(&self, max_age: u64) -> String {
+        let Self {
+            nonce,
+            time,
+            valid,
+            ip,
+            hash: [high, low],
+            zeros,
+        } = self;
+        let expires = time.saturating_add(max_age);
+        format!(
+            r#"{{"nonce":"{nonce:016x}","time":{time},"expires":{expires},"valid":{valid},"ip":"{ip}","hash":"{high:032x}{low:032x}","zeros":{zeros}}}"#
+        )
+    }
+}
+
+fn show(token: &str, max_age: u64, json: bool) {
+    let shown = match Shown::new(token, max_age) {
+        Ok(shown) => shown,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
     };
-    if decoded.len() != 32 {
-        eprintln!("wrong length");
+    if json {
+        println!("{}", shown.json(max_age));
         return;
     }
-    let nonce = u64::from_be_bytes(decoded[0..8].try_into().unwrap());
+    let Shown {
+        nonce,
+        time,
+        valid,
+        ip,
+        hash: [high, low],
+        zeros,
+    } = shown;
     println!("nonce: {nonce:016x}");
-    let time = u64::from_le_bytes(decoded[8..16].try_into().unwrap());
     let ptime: DateTime<Utc> = (UNIX_EPOCH + Duration::from_secs(time)).into();
-    let etime: DateTime<Utc> = (UNIX_EPOCH + Duration::from_secs(time + 604_800)).into();
+    let etime: DateTime<Utc> =
+        (UNIX_EPOCH + Duration::from_secs(time.saturating_add(max_age))).into();
     println!(
         "time: {time} ({}, not before {ptime}, not after {etime})",
-        if unixtime().checked_sub(time).unwrap_or(621_926) > 604_800 {
-            "expired"
-        } else {
-            "valid"
-        }
+        if valid { "valid" } else { "expired" }
     );
-    let ip = Ipv6Addr::from_bits(u128::from_le_bytes(decoded[16..32].try_into().unwrap()));
     println!("ip: {ip}");
-    let hash = Sha256::digest(&decoded);
-    let high = u128::from_be_bytes(hash[0..16].try_into().unwrap());
-    let low = u128::from_be_bytes(hash[16..32].try_into().unwrap());
     println!("hash: {high:032x}{low:032x}");
-    let mut zeros = high.leading_zeros();
-    if zeros == 128 {
-        zeros += low.leading_zeros();
-    }
     println!("zeros: {zeros}");
 }
 
+#[test]
+fn batch() {
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    let challenge: [u8; 24] = gen_challenge(&ip, 1).try_into().unwrap();
+    let nonces = find_nonces(challenge, 4, 5);
+    assert_eq!(nonces.len(), 5);
+    assert!(nonces.is_sorted_by(|a, b| a < b));
+    assert!(nonces.iter().all(|n| check(*n, challenge, 4)));
+
+    let mut combined = nonces[0].to_be_bytes().to_vec();
+    combined.extend(challenge);
+    let token = BASE64_STANDARD.encode(&combined);
+    let shown = Shown::new(&token, 10).unwrap();
+    assert!(!shown.valid);
+    assert!(shown.zeros >= 4);
+    let json = shown.json(10);
+    assert!(json.starts_with(&format!(r#"{{"nonce":"{:016x}","time":1,"expires":11,"valid":false,"ip":"::ffff:192.0.2.1","hash":""#, nonces[0])));
+    assert!(json.ends_with(&format!(r#"","zeros":{}}}"#, shown.zeros)));
+}
+
+fn verify_token(token: &str, difficulty: u8, max_age: u64) -> Result<(), &'static str> {
+    if difficulty > 128 {
+        return Err("i cut corners so difficulty > 128 is not supported");
+    }
+    let decoded = BASE64_STANDARD
+        .decode(token)
+        .map_err(|_| "could not decode base64")?;
+    let decoded: [u8; 32] = decoded.try_into().map_err(|_| "wrong length")?;
+    let nonce = u64::from_be_bytes(decoded[0..8].try_into().unwrap());
+    let challenge: [u8; 24] = decoded[8..32].try_into().unwrap();
+    if !check(nonce, challenge, difficulty) {
+        return Err("not enough leading zeros");
+    }
+    let time = u64::from_le_bytes(decoded[8..16].try_into().unwrap());
+    match unixtime().checked_sub(time) {
+        None => Err("not valid yet"),
+        Some(age) if age > max_age => Err("expired"),
+        Some(_) => Ok(()),
+    }
+}
+
+#[test]
+fn verify_tokens() {
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    let token = |time: u64, good: bool| {
+        let challenge: [u8; 24] = gen_challenge(&ip, time).try_into().unwrap();
+        let nonce = if good {
+            find_nonces(challenge, 4, 1)[0]
+        } else {
+            (0..u64::MAX).find(|&n| !check(n, challenge, 4)).unwrap()
+        };
+        let mut combined = nonce.to_be_bytes().to_vec();
+        combined.extend(challenge);
+        BASE64_STANDARD.encode(&combined)
+    };
+    let now = unixtime();
+
+    assert_eq!(verify_token(&token(now, true), 4, 60), Ok(()));
+    assert_eq!(
+        verify_token(&token(now, false), 4, 60),
+        Err("not enough leading zeros")
+    );
+    assert_eq!(verify_token(&token(now - 100, true), 4, 60), Err("expired"));
+    assert_eq!(
+        verify_token(&token(now + 1000, true), 4, 60),
+        Err("not valid yet")
+    );
+    assert_eq!(verify_token("meow!", 4, 60), Err("could not decode base64"));
+    assert_eq!(
+        verify_token(&BASE64_STANDARD.encode([0; 31]), 4, 60),
+        Err("wrong length")
+    );
+    assert!(verify_token(&token(now, true), 129, 60).is_err());
+}
+
 pub fn run(args: &Args) {
     match &args.action {
         Actions::Generate(GenerateAction {
+            json,
+            count,
             ip,
             difficulty,
             time,
-        }) => generate(ip, *difficulty, *time),
-        Actions::Show(ShowAction { token }) => show(token),
+        }) => generate(ip, *difficulty, *time, *count, *json),
+        Actions::Show(ShowAction {
+            max_age,
+            json,
+            token,
+        }) => show(token, *max_age, *json),
+        Actions::Verify(VerifyAction {
+            max_age,
+            token,
+            difficulty,
+        }) => match verify_token(token, *difficulty, *max_age) {
+            Ok(()) => println!("valid"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
     }
 }
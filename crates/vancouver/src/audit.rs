@@ -2,17 +2,35 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::types::Error;
+use crate::{OutputFormat, merge::DupeKey, types::Error};
+use serde::Serialize;
+use serde_json::json;
 use std::{
     io::{Seek, Write},
     process::ExitCode,
 };
 use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, value};
 
+/// what got recorded, for json output
+#[derive(Serialize)]
+struct Added<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    audit: &'a DupeKey,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<&'a str>,
+}
+
 pub fn add_audit(args: &crate::AuditArgs) -> Result<ExitCode, Error> {
+    if args.output == OutputFormat::Sarif {
+        return Err(Error::UnsupportedOutput);
+    }
     if args.fail && args.base.is_some() {
         return Err(Error::FailAndBase);
     }
+    if args.require_notes && args.notes.is_none() {
+        return Err(Error::NotesRequired);
+    }
 
     let mut file = std::fs::OpenOptions::new()
         .read(true)
@@ -40,14 +58,25 @@ pub fn add_audit(args: &crate::AuditArgs) -> Result<ExitCode, Error> {
         return Err(Error::TomlBorked);
     };
 
+    let mut added = DupeKey {
+        name: args.name.clone(),
+        criteria: args.criteria.clone(),
+        delta: None,
+        version: None,
+        violation: None,
+    };
     let mut t = Table::new();
     if args.fail {
         assert!(args.base.is_none());
         t["violation"] = value(&args.version);
+        added.violation = Some(args.version.clone());
     } else if let Some(base) = &args.base {
-        t["delta"] = value(format!("{base} -> {}", args.version));
+        let delta = format!("{base} -> {}", args.version);
+        t["delta"] = value(&delta);
+        added.delta = Some(delta);
     } else {
         t["version"] = value(&args.version);
+        added.version = Some(args.version.clone());
     }
     t["criteria"] = value(&args.criteria);
     if let Some(notes) = &args.notes {
@@ -60,6 +89,16 @@ pub fn add_audit(args: &crate::AuditArgs) -> Result<ExitCode, Error> {
     file.write_all(toml.to_string().as_bytes())
         .map_err(Error::AuditsWrite)?;
 
+    if args.output == OutputFormat::Json {
+        println!(
+            "{}",
+            json!(Added {
+                name: &added.name,
+                audit: &added,
+                notes: args.notes.as_deref(),
+            })
+        );
+    }
     eprintln!("added :3");
     Ok(ExitCode::SUCCESS)
 }
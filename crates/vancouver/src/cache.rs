@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2025 xfnw
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{metadata::Dependency, types::Date};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+/// where to remember the last passing check, inside cargo's target
+/// directory so cargo clean gets rid of it
+pub fn path(target_directory: &Path) -> PathBuf {
+    target_directory.join("vancouver-cache")
+}
+
+/// hash everything that can change the outcome of a check
+///
+/// the dependencies stand in for the lockfile, and the date is
+/// included since exempts can expire
+pub fn key(
+    args: &crate::CheckArgs,
+    config: &str,
+    audits: &str,
+    today: Date,
+    dependencies: &[Dependency],
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    config.hash(&mut hasher);
+    audits.hash(&mut hasher);
+    today.to_string().hash(&mut hasher);
+    args.recursion_limit.hash(&mut hasher);
+    args.ignore_exempts.hash(&mut hasher);
+    for dependency in dependencies {
+        dependency.name.hash(&mut hasher);
+        dependency.version.to_string().hash(&mut hasher);
+        dependency.source.hash(&mut hasher);
+        dependency.registry.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// how many crates passed last time, if the key matches
+pub fn lookup(path: &Path, key: u64) -> Option<usize> {
+    let cached = std::fs::read_to_string(path).ok()?;
+    let (cached_key, total) = cached.trim_end().split_once(' ')?;
+    if u64::from_str_radix(cached_key, 16).ok()? != key {
+        return None;
+    }
+    total.parse().ok()
+}
+
+/// remember a passing check, ignoring errors since the cache is only
+/// an optimization
+pub fn store(path: &Path, key: u64, total: usize) {
+    // write then rename, so a concurrent check never sees half a file
+    let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+    if std::fs::write(&temp, format!("{key:016x} {total}\n")).is_ok() {
+        _ = std::fs::rename(&temp, path);
+    }
+}
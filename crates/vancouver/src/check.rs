@@ -5,15 +5,18 @@
 use crate::{
     OutputFormat,
     de::string_or_bset,
-    types::{Error, Version},
+    metadata::{CRATES_IO, Dependency, SourceFilter, SourceKind},
+    types::{Cycle, Date, Error, Version, VersionRange},
 };
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
 use serde_json::json;
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, BTreeSet},
-    fs::read_to_string,
     io::{Seek, Write},
+    iter,
+    path::Path,
     process::ExitCode,
     sync::atomic::{AtomicBool, Ordering},
 };
@@ -28,10 +31,21 @@ struct Config {
     /// policy for crates without a policy specified
     #[serde(default, alias = "default-policy")]
     default_policy: PolicyLayer,
+    /// policy for each crate
+    ///
+    /// keys can also be `prefix*` globs. an exact name wins over any
+    /// glob, then the glob with the longest prefix, and the default
+    /// policy is only used when nothing matches
     #[serde(default)]
     policy: BTreeMap<String, PolicyLayer>,
+    /// versions to trust without an audit
+    ///
+    /// keys can be globs like in `policy`, the most specific key with
+    /// an exemption for the version is used
     #[serde(default)]
     exempt: BTreeMap<String, BTreeSet<Audit>>,
+    #[serde(default)]
+    trust: BTreeMap<String, BTreeSet<Trust>>,
 }
 
 #[derive(Debug, Deserialize, Default, Clone)]
@@ -46,11 +60,29 @@ struct Policy {
 }
 
 #[derive(Debug, Deserialize)]
+#[expect(clippy::struct_field_names)]
 struct Audits {
     /// criteria that can be overridden by the config file
     #[serde(default)]
     criteria: BTreeMap<String, Criteria>,
     audits: BTreeMap<String, BTreeSet<Audit>>,
+    #[serde(default)]
+    trust: BTreeMap<String, BTreeSet<Trust>>,
+}
+
+/// trust every version of a crate by some publisher
+#[derive(Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Trust {
+    /// the criteria trusted for
+    criteria: String,
+    /// who publishes the crate, like `github:xfnw`
+    ///
+    /// this is for the humans reading the audits, it is not checked
+    /// against the registry
+    publisher: String,
+    /// do not warn when this trust is unused
+    #[serde(default, alias = "allow-unused")]
+    allow_unused: bool,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -64,10 +96,10 @@ struct Audit {
     /// the version for a standalone audit of an entire version
     #[serde(default)]
     version: Option<String>,
-    /// the version for a standalone violation of an entire version
+    /// the versions affected by a violation
     ///
-    /// unlike cargo-vet, this must be a single specific version,
-    /// rather than a version range
+    /// this is either a single specific version or a simple range like
+    /// `>=1.0.0, <1.2.4`
     #[serde(default)]
     violation: Option<String>,
     /// do not warn when this is an unused exemption
@@ -76,6 +108,15 @@ struct Audit {
     /// config, unused audits do not cause warnings
     #[serde(default, alias = "allow-unused")]
     allow_unused: bool,
+    /// stop trusting this exemption after the given date
+    ///
+    /// this is only meaningful when specified on exemptions in the
+    /// config
+    #[serde(default)]
+    expires: Option<Date>,
+    /// what the auditor had to say
+    #[serde(default)]
+    notes: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -115,6 +156,12 @@ impl TrustRoot {
     }
 }
 
+#[derive(Debug)]
+struct TrustPublisher {
+    publisher: String,
+    used: UsedMarker,
+}
+
 #[derive(Debug)]
 struct TrustDelta {
     parent_version: Version,
@@ -146,6 +193,27 @@ impl<'a> Iterator for CriteriaConsIter<'a> {
 type CriteriaMap<T> = BTreeMap<String, T>;
 type DepMap<T> = BTreeMap<String, T>;
 
+/// the keys that could apply to a dependency, most specific first
+///
+/// this is the exact name, and then `prefix*` globs from the longest
+/// prefix down to a lone `*`
+fn pattern_keys(name: &str) -> impl Iterator<Item = Cow<'_, str>> {
+    let globs = (name.char_indices().map(|(i, _)| i))
+        .chain([name.len()])
+        .rev()
+        .map(|i| Cow::Owned(format!("{}*", &name[..i])));
+    iter::once(Cow::Borrowed(name)).chain(globs)
+}
+
+/// find the most specific value for a dependency that `found` accepts
+fn get_matching<'a, T, U>(
+    map: &'a DepMap<T>,
+    name: &str,
+    found: impl Fn(&'a T) -> Option<U>,
+) -> Option<U> {
+    pattern_keys(name).find_map(|k| map.get(&*k).and_then(&found))
+}
+
 #[derive(Debug)]
 struct TrustMap<T>(CriteriaMap<DepMap<BTreeMap<Version, T>>>);
 
@@ -169,35 +237,43 @@ impl<T> TrustMap<T> {
             .and_then(|d| d.get(name))
             .and_then(|v| v.get(version))
     }
+
+    /// like [`Self::get`], but also looking through glob keys
+    fn get_matching(&self, criteria: &str, name: &str, version: &Version) -> Option<&T> {
+        let deps = self.0.get(criteria)?;
+        get_matching(deps, name, |v| v.get(version))
+    }
 }
 
 #[derive(Debug, Default)]
-struct ViolationMap(CriteriaMap<DepMap<BTreeSet<Version>>>);
+struct ViolationMap(CriteriaMap<DepMap<Vec<VersionRange>>>);
 
 impl ViolationMap {
-    fn insert(&mut self, criteria: String, name: String, version: Version) {
+    fn insert(&mut self, criteria: String, name: String, range: VersionRange) {
         self.0
             .entry(criteria)
             .or_default()
             .entry(name)
             .or_default()
-            .insert(version);
+            .push(range);
     }
 
     fn contains(&self, criteria: &str, name: &str, version: &Version) -> bool {
         self.0
             .get(criteria)
             .and_then(|d| d.get(name))
-            .is_some_and(|v| v.contains(version))
+            .is_some_and(|v| v.iter().any(|r| r.contains(version)))
     }
 }
 
 #[derive(Debug)]
 struct Rules {
     trust_roots: TrustMap<TrustRoot>,
+    trust_publishers: CriteriaMap<DepMap<TrustPublisher>>,
     trust_deltas: TrustMap<TrustDelta>,
     violations: ViolationMap,
     extra_unused: BTreeSet<UnusedExempt>,
+    expired: BTreeSet<ExpiredExempt>,
     implied_all: CriteriaMap<BTreeSet<String>>,
     implied_any: CriteriaMap<BTreeSet<String>>,
     default_policy: Policy,
@@ -206,7 +282,7 @@ struct Rules {
 
 impl Rules {
     #[allow(clippy::too_many_lines)]
-    fn new(mut config: Config, audits: Audits) -> Result<Self, Error> {
+    fn new(mut config: Config, audits: Audits, today: Date) -> Result<Self, Error> {
         let mut criteria = audits.criteria;
         criteria.append(&mut config.criteria);
 
@@ -229,19 +305,36 @@ impl Rules {
             implied_any.entry(criteria).or_default().append(&mut any);
         }
 
+        if let Some(cycle) = find_cycle(&implied_all, &implied_any) {
+            return Err(Error::CriteriaCycle(Cycle(cycle)));
+        }
+
         let mut trust_roots = TrustMap::<TrustRoot>::new();
         let mut trust_deltas = TrustMap::<TrustDelta>::new();
         let mut violations = ViolationMap::default();
+        let mut expired = BTreeSet::new();
 
         for (name, aset) in config.exempt {
             for Audit {
                 criteria,
                 version,
                 allow_unused,
+                expires,
                 ..
             } in aset
             {
                 if let Some(version) = &version {
+                    if let Some(expires) = expires
+                        && expires < today
+                    {
+                        expired.insert(ExpiredExempt {
+                            name: name.clone(),
+                            version: version.clone(),
+                            criteria,
+                            expires,
+                        });
+                        continue;
+                    }
                     trust_roots.insert(
                         criteria.clone(),
                         name.clone(),
@@ -254,6 +347,24 @@ impl Rules {
             }
         }
 
+        let mut trust_publishers: CriteriaMap<DepMap<_>> = BTreeMap::new();
+        for (name, tset) in audits.trust.into_iter().chain(config.trust) {
+            for Trust {
+                criteria,
+                publisher,
+                allow_unused,
+            } in tset
+            {
+                trust_publishers.entry(criteria).or_default().insert(
+                    name.clone(),
+                    TrustPublisher {
+                        publisher,
+                        used: UsedMarker(Some(allow_unused.into())),
+                    },
+                );
+            }
+        }
+
         let mut extra_unused = BTreeSet::new();
 
         for (name, aset) in audits.audits {
@@ -295,7 +406,7 @@ impl Rules {
                     });
                 }
                 if let Some(violation) = &violation {
-                    violations.insert(criteria, name.clone(), Version::new(violation));
+                    violations.insert(criteria, name.clone(), violation.parse()?);
                 }
             }
         }
@@ -323,9 +434,11 @@ impl Rules {
 
         Ok(Self {
             trust_roots,
+            trust_publishers,
             trust_deltas,
             violations,
             extra_unused,
+            expired,
             implied_all,
             implied_any,
             default_policy,
@@ -334,7 +447,7 @@ impl Rules {
     }
 
     fn get_policy(&self, name: &str) -> &Policy {
-        self.policy.get(name).unwrap_or(&self.default_policy)
+        get_matching(&self.policy, name, Some).unwrap_or(&self.default_policy)
     }
 
     fn check_criteria(
@@ -350,14 +463,14 @@ impl Rules {
 
         if let Some(trust) = self
             .trust_roots
-            .get(criteria, name, version)
+            .get_matching(criteria, name, version)
             .filter(|_| !is_violation)
             .or_else(|| {
                 implied_criteria
                     .iter()
                     .flat_map(|c| c.iter())
                     .filter(|&cr| !self.violations.contains(cr, name, version))
-                    .find_map(|cr| self.trust_roots.get(cr, name, version))
+                    .find_map(|cr| self.trust_roots.get_matching(cr, name, version))
             })
         {
             if ignore_exempts && trust.is_exempt() {
@@ -367,6 +480,22 @@ impl Rules {
             return CheckResult::Validated;
         }
 
+        // any version goes, but violations still win
+        if let Some(trust) = Some(criteria)
+            .filter(|_| !is_violation)
+            .into_iter()
+            .chain(
+                implied_criteria
+                    .iter()
+                    .flat_map(|c| c.iter())
+                    .filter(|&cr| !self.violations.contains(cr, name, version)),
+            )
+            .find_map(|cr| self.trust_publishers.get(cr).and_then(|d| d.get(name)))
+        {
+            trust.used.mark_used();
+            return CheckResult::Validated;
+        }
+
         if recursion_limit == 0 {
             if is_violation {
                 return CheckResult::Violation;
@@ -480,12 +609,18 @@ impl Rules {
 
     fn check(
         &self,
-        name: String,
-        version: Version,
+        dependency: Dependency,
         recursion_limit: usize,
         ignore_exempts: bool,
         suggest_via_exempt: bool,
     ) -> Receipt {
+        let Dependency {
+            name,
+            version,
+            source,
+            registry,
+            via,
+        } = dependency;
         let Policy { require_all } = self.get_policy(&name);
 
         let fails: Vec<_> = require_all
@@ -523,6 +658,9 @@ impl Rules {
         Receipt {
             name,
             version,
+            source,
+            registry,
+            via,
             status,
         }
     }
@@ -551,6 +689,73 @@ impl Rules {
 
         out
     }
+
+    fn unused_trusts(&self) -> BTreeSet<UnusedTrust> {
+        self.trust_publishers
+            .iter()
+            .flat_map(|(criteria, map)| {
+                map.iter()
+                    .filter(|(_, t)| {
+                        t.used
+                            .0
+                            .as_ref()
+                            .is_some_and(|b| !b.load(Ordering::Relaxed))
+                    })
+                    .map(|(dep, t)| UnusedTrust {
+                        name: dep.clone(),
+                        publisher: t.publisher.clone(),
+                        criteria: criteria.clone(),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// find a loop in the criteria implication graph
+///
+/// `check_criteria` walks from a criteria to everything that could imply
+/// it, so a loop there would only stop once the recursion limit is
+/// reached
+fn find_cycle(
+    implied_all: &CriteriaMap<BTreeSet<String>>,
+    implied_any: &CriteriaMap<BTreeSet<String>>,
+) -> Option<Vec<String>> {
+    fn visit<'a>(
+        criteria: &'a str,
+        implied_all: &'a CriteriaMap<BTreeSet<String>>,
+        implied_any: &'a CriteriaMap<BTreeSet<String>>,
+        stack: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(pos) = stack.iter().position(|&c| c == criteria) {
+            return Some(stack[pos..].iter().map(ToString::to_string).collect());
+        }
+        if done.contains(criteria) {
+            return None;
+        }
+
+        stack.push(criteria);
+        let next = implied_all
+            .get(criteria)
+            .into_iter()
+            .chain(implied_any.get(criteria))
+            .flatten();
+        for n in next {
+            if let Some(cycle) = visit(n, implied_all, implied_any, stack, done) {
+                return Some(cycle);
+            }
+        }
+        stack.pop();
+        done.insert(criteria);
+
+        None
+    }
+
+    let mut done = BTreeSet::new();
+    implied_all
+        .keys()
+        .chain(implied_any.keys())
+        .find_map(|c| visit(c, implied_all, implied_any, &mut vec![], &mut done))
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
@@ -560,7 +765,54 @@ struct UnusedExempt {
     criteria: String,
 }
 
-fn parse_delta(delta: &str) -> Result<(Version, Version), Error> {
+/// an audit without notes, for --require-notes
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct MissingNotes {
+    name: String,
+    /// the version, delta, or violation that was audited
+    audited: String,
+    criteria: String,
+}
+
+fn missing_notes(audits: &Audits) -> BTreeSet<MissingNotes> {
+    let mut missing = BTreeSet::new();
+    for (name, aset) in &audits.audits {
+        for audit in aset.iter().filter(|a| a.notes.is_none()) {
+            let audited = if let Some(version) = &audit.version {
+                version.clone()
+            } else if let Some(delta) = &audit.delta {
+                delta.clone()
+            } else if let Some(violation) = &audit.violation {
+                format!("violation {violation}")
+            } else {
+                String::new()
+            };
+            missing.insert(MissingNotes {
+                name: name.clone(),
+                audited,
+                criteria: audit.criteria.clone(),
+            });
+        }
+    }
+    missing
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct UnusedTrust {
+    name: String,
+    publisher: String,
+    criteria: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct ExpiredExempt {
+    name: String,
+    version: String,
+    criteria: String,
+    expires: Date,
+}
+
+pub fn parse_delta(delta: &str) -> Result<(Version, Version), Error> {
     let Some((prev, next)) = delta.split_once("->") else {
         return Err(Error::ParseDelta(delta.to_string()));
     };
@@ -586,6 +838,26 @@ enum FailReason {
     Violation,
 }
 
+impl FailReason {
+    const ALL: [Self; 3] = [Self::Missing, Self::RecursionLimitReached, Self::Violation];
+
+    fn rule_id(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::RecursionLimitReached => "recursion-limit-reached",
+            Self::Violation => "violation",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Missing => "dependency has not been audited for the required criteria",
+            Self::RecursionLimitReached => "recursion limit reached while checking criteria",
+            Self::Violation => "dependency matched a violation in the audits file",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct Fail {
     needed: String,
@@ -625,54 +897,171 @@ impl Serialize for Status {
 struct Receipt {
     name: String,
     version: Version,
+    source: SourceKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registry: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    via: Vec<String>,
     #[serde(flatten)]
     status: Status,
 }
 
+/// build a minimal SARIF 2.1.0 log with a result for each failed
+/// criteria, pointing at Cargo.lock
+fn sarif(receipts: &[Receipt]) -> serde_json::Value {
+    let rules: Vec<_> = FailReason::ALL
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.rule_id(),
+                "shortDescription": { "text": r.description() },
+            })
+        })
+        .collect();
+    let results: Vec<_> = receipts
+        .iter()
+        .filter_map(|r| match &r.status {
+            Status::Passed => None,
+            Status::Failed(fails) => Some((r, fails)),
+        })
+        .flat_map(|(r, fails)| {
+            fails.iter().map(move |f| {
+                json!({
+                    "ruleId": f.reason.rule_id(),
+                    "level": "error",
+                    "message": {
+                        "text": format!("{} {} needs {}", r.name, r.version, f.needed),
+                    },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "Cargo.lock" },
+                        },
+                    }],
+                })
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
 #[allow(clippy::too_many_lines)]
 pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
-    let dependencies = crate::metadata::get_dependencies(args.manifest.as_deref())?;
+    let filter = SourceFilter {
+        git: args.include_git,
+        local: args.include_local,
+        ..Default::default()
+    }
+    .with_registries(&args.registry);
+    let config_stdin = is_stdin(&args.config);
+    if config_stdin && (args.add_exempts || args.ratchet) {
+        return Err(Error::ConfigNotWritable);
+    }
+    if config_stdin && is_stdin(&args.audits) {
+        return Err(Error::StdinTwice);
+    }
+    if args.ratchet && !args.target.is_empty() {
+        return Err(Error::RatchetWithTarget);
+    }
+
+    let config_text = read_to_string(&args.config).map_err(Error::ConfigOpen)?;
+    let config: Config = toml_edit::de::from_str(&config_text)?;
+    let audits_text = read_to_string(&args.audits).map_err(Error::AuditsOpen)?;
+    let audits: Audits = toml_edit::de::from_str(&audits_text)?;
+    let missing_notes = if args.require_notes {
+        missing_notes(&audits)
+    } else {
+        BTreeSet::new()
+    };
+
+    let today = Date::today();
+    let rules = Rules::new(config, audits, today)?;
+
+    if args.expired {
+        return report_expired(args, &rules.expired);
+    }
+
+    let metadata = crate::metadata::get_metadata(args.manifest.as_deref(), &filter, &args.target)?;
+    let dependencies = metadata.dependencies;
     if dependencies.is_empty() {
         return Err(Error::EmptyDependencies);
     }
 
-    let config = read_to_string(&args.config).map_err(Error::ConfigOpen)?;
-    let config: Config = toml_edit::de::from_str(&config)?;
-    let audits = read_to_string(&args.audits).map_err(Error::AuditsOpen)?;
-    let audits: Audits = toml_edit::de::from_str(&audits)?;
-
-    let rules = Rules::new(config, audits)?;
+    // only human output of a clean pass gets cached, since that is
+    // short enough to be remembered
+    let cache = (!args.no_cache
+        && !args.ratchet
+        && args.output == OutputFormat::Human
+        && missing_notes.is_empty())
+    .then(|| {
+        (
+            crate::cache::path(&metadata.target_directory),
+            crate::cache::key(args, &config_text, &audits_text, today, &dependencies),
+        )
+    });
+    if let Some((path, key)) = &cache
+        && let Some(total) = crate::cache::lookup(path, *key)
+    {
+        eprintln!("all {total} crates ok (cached)");
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    let receipts: Vec<_> = dependencies
-        .into_par_iter()
-        .map(|(name, version)| {
-            rules.check(
-                name,
-                version,
-                args.recursion_limit,
-                args.ignore_exempts,
-                args.suggest_via_exempt,
-            )
-        })
-        .collect();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or_default())
+        .build()?;
+    let mut receipts: Vec<_> = pool.install(|| {
+        dependencies
+            .into_par_iter()
+            .map(|dependency| {
+                rules.check(
+                    dependency,
+                    args.recursion_limit,
+                    args.ignore_exempts,
+                    args.suggest_via_exempt,
+                )
+            })
+            .collect()
+    });
+    receipts.sort_by(|l, r| l.name.cmp(&r.name).then_with(|| l.version.cmp(&r.version)));
     let total = receipts.len();
     let unused = rules.unused_exempts();
+    let unused_trusts = rules.unused_trusts();
+    let nothing_unused = unused.is_empty() && unused_trusts.is_empty() && rules.expired.is_empty();
 
     if args.output == OutputFormat::Json {
         let passed = receipts
             .iter()
             .filter(|&r| matches!(r.status, Status::Passed))
             .count();
-        println!(
-            "{}",
-            json!({
-                "dependencies": receipts,
-                "total": total,
-                "total_failed": total - passed,
-                "total_passed": passed,
-                "unused_exempts": unused,
-            })
-        );
+        let mut out = json!({
+            "dependencies": receipts,
+            "total": total,
+            "total_failed": total - passed,
+            "total_passed": passed,
+            "unused_exempts": unused,
+            "unused_trusts": unused_trusts,
+            "expired_exempts": rules.expired,
+        });
+        if args.require_notes {
+            out["missing_notes"] = json!(missing_notes);
+        }
+        println!("{out}");
+    } else if args.output == OutputFormat::Sarif {
+        println!("{}", sarif(&receipts));
     }
 
     let fails: Vec<_> = receipts
@@ -699,7 +1088,40 @@ pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
         }
     }
 
+    if args.output == OutputFormat::Human {
+        for UnusedTrust {
+            name,
+            publisher,
+            criteria,
+        } in unused_trusts
+        {
+            println!("unused trust: {name} {publisher} {criteria}");
+        }
+    }
+
+    if args.output == OutputFormat::Human {
+        print_expired(&rules.expired);
+        for MissingNotes {
+            name,
+            audited,
+            criteria,
+        } in &missing_notes
+        {
+            println!("missing notes: {name} {audited} {criteria}");
+        }
+    }
+
+    if fails.is_empty() && !missing_notes.is_empty() {
+        eprintln!("{} audits are missing notes", missing_notes.len());
+        return Ok(ExitCode::FAILURE);
+    }
+
     if fails.is_empty() {
+        if let Some((path, key)) = &cache
+            && nothing_unused
+        {
+            crate::cache::store(path, *key, total);
+        }
         eprintln!("all {total} crates ok");
         return Ok(ExitCode::SUCCESS);
     }
@@ -720,10 +1142,16 @@ pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
         for Receipt {
             name,
             version,
+            registry,
+            via,
             status,
+            ..
         } in &fails
         {
             println!("{name} {version}");
+            if !via.is_empty() {
+                println!(" via {}", via.join(" -> "));
+            }
             match status {
                 Status::Passed => unreachable!(),
                 Status::Failed(v) => {
@@ -740,15 +1168,21 @@ pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
                                     && let Some(prev) = prev_version
                                 {
                                     println!("  help: found a previous audit for {prev}");
-                                    println!("  review https://diff.rs/{name}/{prev}/{version}");
+                                    if registry.as_deref() == Some(CRATES_IO) {
+                                        println!(
+                                            "  review https://diff.rs/{name}/{prev}/{version}"
+                                        );
+                                    }
                                     println!(
                                         "  then vancouver audit {name} -b {prev} {version} {needed}"
                                     );
                                 } else {
                                     println!("  help: could not find previous audits :(");
-                                    println!(
-                                        "  review https://docs.rs/crate/{name}/{version}/source/"
-                                    );
+                                    if registry.as_deref() == Some(CRATES_IO) {
+                                        println!(
+                                            "  review https://docs.rs/crate/{name}/{version}/source/"
+                                        );
+                                    }
                                     println!("  then vancouver audit {name} {version} {needed}");
                                 }
                             }
@@ -759,8 +1193,7 @@ pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
                                     println!("  or increase the limit with --recursion-limit");
                                 } else {
                                     println!("  help: no acceptable previous audits");
-                                    println!("  you might have a loop of criteria implies");
-                                    println!("  or delta audits somewhere");
+                                    println!("  you might have a loop of delta audits somewhere");
                                 }
                             }
                             FailReason::Violation => {
@@ -781,6 +1214,50 @@ pub fn do_check(args: &crate::CheckArgs) -> Result<ExitCode, Error> {
     Ok(ExitCode::FAILURE)
 }
 
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// read a file, or stdin when the path is `-`
+fn read_to_string(path: &Path) -> std::io::Result<String> {
+    if is_stdin(path) {
+        return std::io::read_to_string(std::io::stdin());
+    }
+    std::fs::read_to_string(path)
+}
+
+fn print_expired(expired: &BTreeSet<ExpiredExempt>) {
+    for ExpiredExempt {
+        name,
+        version,
+        criteria,
+        expires,
+    } in expired
+    {
+        println!("expired exempt: {name} {version} {criteria} (expired {expires})");
+    }
+}
+
+fn report_expired(
+    args: &crate::CheckArgs,
+    expired: &BTreeSet<ExpiredExempt>,
+) -> Result<ExitCode, Error> {
+    match args.output {
+        OutputFormat::Human => print_expired(expired),
+        OutputFormat::Json => println!("{}", json!({ "expired_exempts": expired })),
+        // expired exempts are not findings in any file, so there is no
+        // sensible sarif for them
+        OutputFormat::Sarif => return Err(Error::UnsupportedOutput),
+    }
+
+    if expired.is_empty() {
+        eprintln!("no expired exempts");
+        return Ok(ExitCode::SUCCESS);
+    }
+    eprintln!("{} exempts have expired", expired.len());
+    Ok(ExitCode::FAILURE)
+}
+
 fn write_config(file: &mut std::fs::File, bytes: &[u8]) -> Result<(), Error> {
     file.rewind().map_err(Error::ConfigWrite)?;
     file.set_len(0).map_err(Error::ConfigWrite)?;
@@ -818,6 +1295,7 @@ fn add_exempts(fails: &Vec<Receipt>, toml: &mut DocumentMut) -> Result<(), Error
         name,
         version,
         status,
+        ..
     } in fails
     {
         let Item::ArrayOfTables(arr) = etable
@@ -875,3 +1353,58 @@ fn ratchet_exempts(unused: &BTreeSet<UnusedExempt>, toml: &mut DocumentMut) -> R
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CriteriaMap, DepMap, find_cycle, get_matching};
+    use std::collections::BTreeSet;
+
+    fn graph(edges: &[(&str, &str)]) -> CriteriaMap<BTreeSet<String>> {
+        let mut out: CriteriaMap<BTreeSet<String>> = CriteriaMap::new();
+        for (from, to) in edges {
+            out.entry(from.to_string())
+                .or_default()
+                .insert(to.to_string());
+        }
+        out
+    }
+
+    #[test]
+    fn criteria_cycle() {
+        let all = graph(&[("a", "b"), ("b", "c")]);
+        let any = graph(&[("c", "d"), ("b", "d")]);
+        assert_eq!(find_cycle(&all, &any), None);
+
+        let any = graph(&[("c", "d"), ("d", "b")]);
+        assert_eq!(
+            find_cycle(&all, &any),
+            Some(vec!["b".to_string(), "c".to_string(), "d".to_string()])
+        );
+
+        let any = graph(&[("e", "e")]);
+        assert_eq!(find_cycle(&all, &any), Some(vec!["e".to_string()]));
+    }
+
+    #[test]
+    fn pattern_precedence() {
+        let map: DepMap<&str> = ["*", "tokio*", "tokio-*", "tokio-util", "serde-*"]
+            .into_iter()
+            .map(|k| (k.to_string(), k))
+            .collect();
+        let get = |name| get_matching(&map, name, |v| Some(*v));
+
+        assert_eq!(get("tokio-util"), Some("tokio-util"));
+        assert_eq!(get("tokio-macros"), Some("tokio-*"));
+        assert_eq!(get("tokio"), Some("tokio*"));
+        assert_eq!(get("tokio-"), Some("tokio-*"));
+        assert_eq!(get("serde"), Some("*"));
+        assert_eq!(get(""), Some("*"));
+
+        // a more specific key that does not have what we want is skipped
+        let get = |name| get_matching(&map, name, |v| (*v != "tokio-*").then_some(*v));
+        assert_eq!(get("tokio-macros"), Some("tokio*"));
+
+        let map: DepMap<u8> = [("serde-*".to_string(), 1)].into();
+        assert_eq!(get_matching(&map, "serde", Some), None);
+    }
+}
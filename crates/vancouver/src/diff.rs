@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: 2025 xfnw
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    OutputFormat,
+    merge::{DupeKey, audit_keys},
+    types::Error,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{collections::BTreeMap, fmt, path::Path, process::ExitCode};
+use toml_edit::{DocumentMut, Item, Table};
+
+#[derive(Debug, Default, Serialize)]
+struct CrateDiff {
+    added: Vec<DupeKey>,
+    removed: Vec<DupeKey>,
+    changed: Vec<Changed>,
+}
+
+#[derive(Debug, Serialize)]
+struct Changed {
+    from: DupeKey,
+    to: String,
+}
+
+/// the part of an audit that is not the criteria
+struct Target<'a>(&'a DupeKey);
+
+impl fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(version) = &self.0.version {
+            write!(f, "{version}")
+        } else if let Some(delta) = &self.0.delta {
+            write!(f, "{delta}")
+        } else if let Some(violation) = &self.0.violation {
+            write!(f, "violation {violation}")
+        } else {
+            write!(f, "?")
+        }
+    }
+}
+
+fn read_audits(path: &Path) -> Result<Table, Error> {
+    let toml: DocumentMut = std::fs::read_to_string(path)
+        .map_err(Error::AuditsOpen)?
+        .parse()?;
+    match toml.get("audits") {
+        Some(Item::Table(t)) => Ok(t.clone()),
+        None => Ok(Table::new()),
+        Some(_) => Err(Error::TomlBorked),
+    }
+}
+
+pub fn do_diff(args: &crate::DiffArgs) -> Result<ExitCode, Error> {
+    if args.output == OutputFormat::Sarif {
+        return Err(Error::UnsupportedOutput);
+    }
+
+    let old = audit_keys(&read_audits(&args.old)?);
+    let new = audit_keys(&read_audits(&args.new)?);
+
+    let mut crates: BTreeMap<String, CrateDiff> = BTreeMap::new();
+    let mut added: Vec<_> = new.difference(&old).collect();
+
+    for removed in old.difference(&new) {
+        let entry = crates.entry(removed.name.clone()).or_default();
+        if let Some(pos) = added.iter().position(|a| a.same_target(removed)) {
+            entry.changed.push(Changed {
+                from: removed.clone(),
+                to: added.remove(pos).criteria.clone(),
+            });
+        } else {
+            entry.removed.push(removed.clone());
+        }
+    }
+    for a in added {
+        crates
+            .entry(a.name.clone())
+            .or_default()
+            .added
+            .push(a.clone());
+    }
+
+    if args.output == OutputFormat::Json {
+        println!("{}", json!(crates));
+    } else {
+        for (name, diff) in &crates {
+            println!("{name}");
+            for a in &diff.added {
+                println!(" + {} {}", Target(a), a.criteria);
+            }
+            for r in &diff.removed {
+                println!(" - {} {}", Target(r), r.criteria);
+            }
+            for Changed { from, to } in &diff.changed {
+                println!(" ~ {} {} -> {to}", Target(from), from.criteria);
+            }
+        }
+    }
+
+    if crates.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    Ok(ExitCode::FAILURE)
+}
@@ -6,10 +6,13 @@ use argh::{FromArgs, from_env};
 use std::{path::PathBuf, process::ExitCode};
 
 mod audit;
+mod cache;
 mod check;
 mod de;
+mod diff;
 mod merge;
 mod metadata;
+mod prune;
 mod types;
 
 /// dependency auditing that meows
@@ -26,6 +29,8 @@ enum Cmds {
     Check(CheckArgs),
     Audit(AuditArgs),
     Merge(MergeArgs),
+    Prune(PruneArgs),
+    Diff(DiffArgs),
 }
 
 /// do a checkup on your dependencies
@@ -33,20 +38,27 @@ enum Cmds {
 #[argh(subcommand)]
 #[argh(name = "check")]
 #[argh(help_triggers("-h", "--help"))]
+#[argh(error_code(1, "some dependencies failed, or something needs attention"))]
+#[argh(error_code(2, "the check could not be done"))]
+#[argh(error_code(3, "exempts were added for failing dependencies"))]
 #[expect(clippy::struct_excessive_bools)]
 pub struct CheckArgs {
     /// path to your cargo manifest
     #[argh(option)]
     manifest: Option<PathBuf>,
-    /// path to your vancouver config
+    /// path to your vancouver config, or - for stdin
     #[argh(option, default = "PathBuf::from(\"vancouver.toml\")")]
     config: PathBuf,
-    /// path to your audits file
+    /// path to your audits file, or - for stdin
     #[argh(option, default = "PathBuf::from(\"audits.toml\")")]
     audits: PathBuf,
     /// stop searching after this many layers of recursion
     #[argh(option, default = "621")]
     recursion_limit: usize,
+    /// how many dependencies to check at once (defaults to the number
+    /// of cpus, 1 checks them sequentially)
+    #[argh(option, short = 'j')]
+    jobs: Option<usize>,
     /// do a check as if there were no exempts in the config
     #[argh(switch)]
     ignore_exempts: bool,
@@ -62,7 +74,33 @@ pub struct CheckArgs {
     /// recommend delta audits going to exempts
     #[argh(switch)]
     suggest_via_exempt: bool,
-    /// the output format to use (human or json)
+    /// only report exempts that are past their expiry date
+    #[argh(switch)]
+    expired: bool,
+    /// check everything again, even if nothing changed since the last
+    /// passing check
+    #[argh(switch)]
+    no_cache: bool,
+    /// also check dependencies from git repositories, versioned by
+    /// their commit
+    #[argh(switch)]
+    include_git: bool,
+    /// also check dependencies from local paths outside of the
+    /// workspace
+    #[argh(switch)]
+    include_local: bool,
+    /// a registry to check dependencies from, can be given multiple
+    /// times (defaults to crates.io)
+    #[argh(option)]
+    registry: Vec<String>,
+    /// only check dependencies needed when building for this target
+    /// triple, can be given multiple times (defaults to every target)
+    #[argh(option)]
+    target: Vec<String>,
+    /// also fail if any audit is missing notes
+    #[argh(switch)]
+    require_notes: bool,
+    /// the output format to use (human, json, or sarif)
     #[argh(option, default = "OutputFormat::Human")]
     output: OutputFormat,
 }
@@ -71,6 +109,7 @@ pub struct CheckArgs {
 enum OutputFormat {
     Human,
     Json,
+    Sarif,
 }
 
 impl std::str::FromStr for OutputFormat {
@@ -79,7 +118,8 @@ impl std::str::FromStr for OutputFormat {
         match s {
             "human" => Ok(Self::Human),
             "json" => Ok(Self::Json),
-            _ => Err("output format must be human or json"),
+            "sarif" => Ok(Self::Sarif),
+            _ => Err("output format must be human, json, or sarif"),
         }
     }
 }
@@ -89,6 +129,7 @@ impl std::str::FromStr for OutputFormat {
 #[argh(subcommand)]
 #[argh(name = "audit")]
 #[argh(help_triggers("-h", "--help"))]
+#[argh(error_code(2, "the audit could not be recorded"))]
 pub struct AuditArgs {
     /// path to your audits file
     #[argh(option, default = "PathBuf::from(\"audits.toml\")")]
@@ -108,9 +149,15 @@ pub struct AuditArgs {
     /// additional notes to include
     #[argh(option, short = 'n')]
     notes: Option<String>,
+    /// refuse to record the audit without notes
+    #[argh(switch)]
+    require_notes: bool,
     /// record a violation
     #[argh(switch, short = 'x')]
     fail: bool,
+    /// the output format to use (human or json)
+    #[argh(option, default = "OutputFormat::Human")]
+    output: OutputFormat,
 }
 
 /// merge audits from another file
@@ -118,6 +165,7 @@ pub struct AuditArgs {
 #[argh(subcommand)]
 #[argh(name = "merge")]
 #[argh(help_triggers("-h", "--help"))]
+#[argh(error_code(2, "the audits could not be merged"))]
 pub struct MergeArgs {
     /// path to your audits file
     #[argh(option, default = "PathBuf::from(\"audits.toml\")")]
@@ -132,6 +180,55 @@ pub struct MergeArgs {
     /// identifier
     #[argh(switch)]
     isolate: bool,
+    /// the output format to use (human or json)
+    #[argh(option, default = "OutputFormat::Human")]
+    output: OutputFormat,
+}
+
+/// remove audits that are no longer needed by any dependency
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+#[argh(name = "prune")]
+#[argh(help_triggers("-h", "--help"))]
+pub struct PruneArgs {
+    /// path to your cargo manifest
+    #[argh(option)]
+    manifest: Option<PathBuf>,
+    /// path to your audits file
+    #[argh(option, default = "PathBuf::from(\"audits.toml\")")]
+    audits: PathBuf,
+    /// only print what would be removed, without editing the audits
+    #[argh(switch)]
+    dry_run: bool,
+    /// keep audits needed by dependencies from git repositories
+    #[argh(switch)]
+    include_git: bool,
+    /// keep audits needed by dependencies from local paths
+    #[argh(switch)]
+    include_local: bool,
+    /// a registry to keep audits for, can be given multiple times
+    /// (defaults to crates.io)
+    #[argh(option)]
+    registry: Vec<String>,
+}
+
+/// show which audits differ between two audits files
+///
+/// exits with 1 if there are any differences
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+#[argh(name = "diff")]
+#[argh(help_triggers("-h", "--help"))]
+pub struct DiffArgs {
+    /// the audits file to compare against
+    #[argh(positional)]
+    old: PathBuf,
+    /// the audits file with changes
+    #[argh(positional)]
+    new: PathBuf,
+    /// the output format to use (human or json)
+    #[argh(option, default = "OutputFormat::Human")]
+    output: OutputFormat,
 }
 
 fn main() -> ExitCode {
@@ -140,6 +237,8 @@ fn main() -> ExitCode {
         Cmds::Check(args) => check::do_check(&args),
         Cmds::Audit(args) => audit::add_audit(&args),
         Cmds::Merge(args) => merge::do_merge(&args),
+        Cmds::Prune(args) => prune::do_prune(&args),
+        Cmds::Diff(args) => diff::do_diff(&args),
     } {
         Ok(c) => c,
         Err(e) => {
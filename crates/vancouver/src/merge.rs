@@ -2,7 +2,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::types::Error;
+use crate::{OutputFormat, types::Error};
+use serde::Serialize;
+use serde_json::json;
 use std::{
     collections::BTreeSet,
     io::{Seek, Write},
@@ -10,17 +12,21 @@ use std::{
 };
 use toml_edit::{ArrayOfTables, DocumentMut, Formatted, Item, Table, Value, value};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct DupeKey {
-    name: String,
-    criteria: String,
-    delta: Option<String>,
-    version: Option<String>,
-    violation: Option<String>,
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct DupeKey {
+    #[serde(skip)]
+    pub name: String,
+    pub criteria: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violation: Option<String>,
 }
 
 impl DupeKey {
-    fn new(name: &str, audit: &Table) -> Option<Self> {
+    pub fn new(name: &str, audit: &Table) -> Option<Self> {
         let Some(Item::Value(Value::String(criteria))) = audit.get("criteria") else {
             return None;
         };
@@ -47,9 +53,51 @@ impl DupeKey {
             violation,
         })
     }
+
+    /// whether both keys are for the same audit, ignoring the criteria
+    pub fn same_target(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.delta == other.delta
+            && self.version == other.version
+            && self.violation == other.violation
+    }
+}
+
+/// collect the keys of every audit in an audits table
+pub fn audit_keys(audits: &Table) -> BTreeSet<DupeKey> {
+    audits
+        .iter()
+        .filter_map(|(key, inner)| {
+            let Item::ArrayOfTables(inner) = inner else {
+                return None;
+            };
+            Some(
+                inner
+                    .iter()
+                    .filter_map(|t| DupeKey::new(key, t))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// how many audits a merge touched, for json output
+#[derive(Debug, Default, Serialize)]
+struct MergeCounts {
+    /// newly copied over
+    added: usize,
+    /// already in the audits file
+    unchanged: usize,
+    /// previously merged from this source, but no longer in it
+    removed: usize,
 }
 
 pub fn do_merge(args: &crate::MergeArgs) -> Result<ExitCode, Error> {
+    if args.output == OutputFormat::Sarif {
+        return Err(Error::UnsupportedOutput);
+    }
+
     let source = std::fs::read_to_string(&args.file).map_err(Error::MergeSourceOpen)?;
     let source: DocumentMut = source.parse()?;
     let Some(Item::Table(source_audits_table)) = source.get("audits") else {
@@ -74,23 +122,10 @@ pub fn do_merge(args: &crate::MergeArgs) -> Result<ExitCode, Error> {
         dest_audits_table.set_implicit(true);
     }
 
-    let existing: BTreeSet<_> = dest_audits_table
-        .iter()
-        .filter_map(|(key, inner)| {
-            let Item::ArrayOfTables(inner) = inner else {
-                return None;
-            };
-            Some(
-                inner
-                    .iter()
-                    .filter_map(|t| DupeKey::new(key, t))
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .flatten()
-        .collect();
+    let existing = audit_keys(dest_audits_table);
 
     let mut merged = BTreeSet::new();
+    let mut counts = MergeCounts::default();
     for (dep, inner) in source_audits_table {
         let Item::ArrayOfTables(inner) = inner else {
             continue;
@@ -120,8 +155,10 @@ pub fn do_merge(args: &crate::MergeArgs) -> Result<ExitCode, Error> {
             let exists = existing.contains(&dup);
             merged.insert(dup);
             if exists {
+                counts.unchanged += 1;
                 continue;
             }
+            counts.added += 1;
             t["merged-from"] = value(args.identifier.clone());
             if args.isolate {
                 t["private"] = value(true);
@@ -142,7 +179,11 @@ pub fn do_merge(args: &crate::MergeArgs) -> Result<ExitCode, Error> {
             let Some(dup) = DupeKey::new(name, t) else {
                 return true;
             };
-            from.value() != &args.identifier || merged.contains(&dup)
+            let keep = from.value() != &args.identifier || merged.contains(&dup);
+            if !keep {
+                counts.removed += 1;
+            }
+            keep
         });
 
         !inner.is_empty()
@@ -157,6 +198,9 @@ pub fn do_merge(args: &crate::MergeArgs) -> Result<ExitCode, Error> {
         .write_all(dest.to_string().as_bytes())
         .map_err(Error::AuditsWrite)?;
 
+    if args.output == OutputFormat::Json {
+        println!("{}", json!(counts));
+    }
     eprintln!("merged :3");
     Ok(ExitCode::SUCCESS)
 }
@@ -3,24 +3,183 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use crate::types::{Error, Version};
-use serde::Deserialize;
-use std::{path::Path, process::Stdio};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
 
-const REGISTRY: &str = "registry+https://github.com/rust-lang/crates.io-index";
+pub const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
 
 #[derive(Debug, Deserialize)]
 struct CargoMetadata {
     packages: Vec<CargoMetadataPackage>,
+    workspace_members: BTreeSet<String>,
+    target_directory: PathBuf,
+    resolve: Option<CargoMetadataResolve>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoMetadataResolve {
+    nodes: Vec<CargoMetadataNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoMetadataNode {
+    id: String,
+    dependencies: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct CargoMetadataPackage {
+    id: String,
     name: String,
     version: String,
     source: Option<String>,
 }
 
-pub fn get_dependencies(manifest: Option<&Path>) -> Result<Vec<(String, Version)>, Error> {
+/// where a dependency comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// a trusted registry, crates.io unless others are given
+    Registry,
+    /// a git repository, pinned to a specific commit
+    Git,
+    /// a local path outside of the workspace
+    Local,
+}
+
+/// which kinds of sources to include
+#[derive(Debug, Clone, Default)]
+pub struct SourceFilter {
+    pub git: bool,
+    pub local: bool,
+    /// source ids of the registries to accept, crates.io if empty
+    pub registries: Vec<String>,
+}
+
+impl SourceFilter {
+    /// turn the registry urls given on the command line into the
+    /// source ids cargo metadata uses
+    pub fn with_registries(mut self, urls: &[String]) -> Self {
+        self.registries = urls
+            .iter()
+            .map(|url| {
+                if url.starts_with("registry+") || url.starts_with("sparse+") {
+                    url.clone()
+                } else {
+                    format!("registry+{url}")
+                }
+            })
+            .collect();
+        self
+    }
+
+    fn is_registry(&self, source: &str) -> bool {
+        if self.registries.is_empty() {
+            return source == CRATES_IO;
+        }
+        self.registries.iter().any(|r| r == source)
+    }
+}
+
+#[derive(Debug)]
+pub struct Dependency {
+    pub name: String,
+    pub version: Version,
+    pub source: SourceKind,
+    /// the source id of the registry it came from, if any
+    pub registry: Option<String>,
+    /// names of the crates along one shortest path from a workspace
+    /// member, ending with this one
+    pub via: Vec<String>,
+}
+
+impl CargoMetadataPackage {
+    fn into_dependency(self, filter: &SourceFilter, via: Vec<String>) -> Option<Dependency> {
+        let mut registry = None;
+        let (source, version) = match self.source {
+            Some(s) if filter.is_registry(&s) => {
+                registry = Some(s);
+                (SourceKind::Registry, Version::new(&self.version))
+            }
+            // git sources look like git+https://example.com/repo?branch=main#commit
+            Some(ref s) if filter.git && s.starts_with("git+") => {
+                let (_, rev) = s.rsplit_once('#')?;
+                (
+                    SourceKind::Git,
+                    Version::new(&format!("{}+{rev}", self.version)),
+                )
+            }
+            None if filter.local => (SourceKind::Local, Version::new(&self.version)),
+            _ => return None,
+        };
+
+        Some(Dependency {
+            name: self.name,
+            version,
+            source,
+            registry,
+            via,
+        })
+    }
+}
+
+/// breadth-first search from the workspace members, mapping each
+/// package id reached to the id it was first reached from
+fn shortest_parents<'a>(
+    members: &'a BTreeSet<String>,
+    nodes: &'a [CargoMetadataNode],
+) -> HashMap<&'a str, &'a str> {
+    let graph: HashMap<_, _> = nodes
+        .iter()
+        .map(|n| (n.id.as_str(), n.dependencies.as_slice()))
+        .collect();
+    let mut seen: HashSet<_> = members.iter().map(String::as_str).collect();
+    let mut queue: VecDeque<_> = members.iter().map(String::as_str).collect();
+    let mut parents = HashMap::new();
+
+    while let Some(id) = queue.pop_front() {
+        for dep in graph.get(id).copied().unwrap_or_default() {
+            if seen.insert(dep) {
+                parents.insert(dep.as_str(), id);
+                queue.push_back(dep);
+            }
+        }
+    }
+
+    parents
+}
+
+/// follow the parents back to a workspace member, giving the names
+/// along the way in order
+fn path_to(id: &str, parents: &HashMap<&str, &str>, names: &HashMap<&str, &str>) -> Vec<String> {
+    let mut path = vec![];
+    let mut current = Some(id);
+    while let Some(id) = current {
+        path.push(names.get(id).copied().unwrap_or(id).to_string());
+        current = parents.get(id).copied();
+    }
+    path.reverse();
+    path
+}
+
+/// the parts of cargo metadata we care about
+#[derive(Debug)]
+pub struct Metadata {
+    pub dependencies: Vec<Dependency>,
+    pub target_directory: PathBuf,
+}
+
+/// get the dependencies, only including ones needed by the given
+/// target triples if there are any
+pub fn get_metadata(
+    manifest: Option<&Path>,
+    filter: &SourceFilter,
+    targets: &[String],
+) -> Result<Metadata, Error> {
     let program = std::env::var("CARGO");
     let program = program.as_deref().unwrap_or("cargo");
     let mut command = std::process::Command::new(program);
@@ -35,6 +194,10 @@ pub fn get_dependencies(manifest: Option<&Path>) -> Result<Vec<(String, Version)
         command.arg("--manifest-path").arg(manifest);
     }
 
+    for target in targets {
+        command.arg("--filter-platform").arg(target);
+    }
+
     let output = command
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
@@ -46,14 +209,64 @@ pub fn get_dependencies(manifest: Option<&Path>) -> Result<Vec<(String, Version)
     }
 
     let lock: CargoMetadata = serde_json::de::from_slice(&output.stdout)?;
+    let members = lock.workspace_members;
+
+    let nodes = lock.resolve.map(|r| r.nodes).unwrap_or_default();
+    let parents = shortest_parents(&members, &nodes);
+    let names: HashMap<_, _> = lock
+        .packages
+        .iter()
+        .map(|p| (p.id.as_str(), p.name.as_str()))
+        .collect();
+    let vias: Vec<_> = lock
+        .packages
+        .iter()
+        .map(|p| path_to(&p.id, &parents, &names))
+        .collect();
 
-    Ok(lock
+    let dependencies = lock
         .packages
         .into_iter()
-        .filter_map(|p| {
-            p.source
-                .is_some_and(|s| s == REGISTRY)
-                .then(|| (p.name, Version::new(&p.version)))
-        })
-        .collect())
+        .zip(vias)
+        .filter(|(p, _)| !members.contains(&p.id))
+        .filter_map(|(p, via)| p.into_dependency(filter, via))
+        .collect();
+
+    Ok(Metadata {
+        dependencies,
+        target_directory: lock.target_directory,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CargoMetadataNode, path_to, shortest_parents};
+    use std::collections::{BTreeSet, HashMap};
+
+    #[test]
+    fn shortest_path() {
+        let edges: &[(&str, &[&str])] = &[
+            ("app", &["a", "b"]),
+            ("a", &["c"]),
+            ("b", &["d"]),
+            ("c", &["d"]),
+            ("d", &["e"]),
+            ("lib", &["e"]),
+        ];
+        let nodes: Vec<_> = edges
+            .iter()
+            .map(|(id, deps)| CargoMetadataNode {
+                id: (*id).to_string(),
+                dependencies: deps.iter().map(ToString::to_string).collect(),
+            })
+            .collect();
+        let members = BTreeSet::from(["app".to_string(), "lib".to_string()]);
+        let parents = shortest_parents(&members, &nodes);
+        let names = HashMap::from([("d", "dee")]);
+
+        assert_eq!(path_to("d", &parents, &names), ["app", "b", "dee"]);
+        assert_eq!(path_to("e", &parents, &names), ["lib", "e"]);
+        assert_eq!(path_to("app", &parents, &names), ["app"]);
+        assert_eq!(path_to("c", &parents, &names), ["app", "a", "c"]);
+    }
 }
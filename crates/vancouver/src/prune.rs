@@ -0,0 +1,159 @@
+// SPDX-FileCopyrightText: 2025 xfnw
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    check::parse_delta,
+    metadata::{Dependency, SourceFilter},
+    types::{Error, Version},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Seek, Write},
+    process::ExitCode,
+};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+fn get_str<'a>(audit: &'a Table, key: &str) -> Option<&'a str> {
+    if let Some(Item::Value(Value::String(s))) = audit.get(key) {
+        Some(s.value())
+    } else {
+        None
+    }
+}
+
+/// find every version of a dependency that a check could end up at,
+/// by following delta audits backwards from the versions in use
+fn reachable_versions(
+    dependencies: Vec<Dependency>,
+    audits: &Table,
+) -> Result<BTreeMap<String, BTreeSet<Version>>, Error> {
+    let mut reachable: BTreeMap<String, BTreeSet<Version>> = BTreeMap::new();
+    for Dependency { name, version, .. } in dependencies {
+        reachable.entry(name).or_default().insert(version);
+    }
+
+    for (name, versions) in &mut reachable {
+        let Some(Item::ArrayOfTables(inner)) = audits.get(name) else {
+            continue;
+        };
+        let deltas = inner
+            .iter()
+            .filter_map(|t| get_str(t, "delta"))
+            .map(parse_delta)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (prev, next) in &deltas {
+                if versions.contains(next) && !versions.contains(prev) {
+                    versions.insert(prev.clone());
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    Ok(reachable)
+}
+
+/// whether an audit could still be used by a check
+///
+/// violations are kept as long as the dependency is, so they keep
+/// guarding against upgrading to a bad version
+fn is_needed(audit: &Table, versions: Option<&BTreeSet<Version>>) -> Result<bool, Error> {
+    let Some(versions) = versions else {
+        return Ok(false);
+    };
+    if let Some(version) = get_str(audit, "version") {
+        return Ok(versions.contains(&Version::new(version)));
+    }
+    if let Some(delta) = get_str(audit, "delta") {
+        return Ok(versions.contains(&parse_delta(delta)?.1));
+    }
+    Ok(true)
+}
+
+fn describe(audit: &Table) -> String {
+    let criteria = get_str(audit, "criteria").unwrap_or_default();
+    if let Some(version) = get_str(audit, "version") {
+        format!("{version} {criteria}")
+    } else if let Some(delta) = get_str(audit, "delta") {
+        format!("{delta} {criteria}")
+    } else if let Some(violation) = get_str(audit, "violation") {
+        format!("violation {violation} {criteria}")
+    } else {
+        criteria.to_string()
+    }
+}
+
+pub fn do_prune(args: &crate::PruneArgs) -> Result<ExitCode, Error> {
+    let filter = SourceFilter {
+        git: args.include_git,
+        local: args.include_local,
+        ..Default::default()
+    }
+    .with_registries(&args.registry);
+    let dependencies =
+        crate::metadata::get_metadata(args.manifest.as_deref(), &filter, &[])?.dependencies;
+    if dependencies.is_empty() {
+        return Err(Error::EmptyDependencies);
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(!args.dry_run)
+        .open(&args.audits)
+        .map_err(Error::AuditsOpen)?;
+    let mut toml: DocumentMut = std::io::read_to_string(&file)
+        .map_err(Error::AuditsOpen)?
+        .parse()?;
+    let Some(Item::Table(atable)) = toml.get_mut("audits") else {
+        return Err(Error::TomlBorked);
+    };
+
+    let reachable = reachable_versions(dependencies, atable)?;
+
+    let mut removed = 0;
+    let mut err = None;
+    atable.retain(|name, inner| {
+        let Item::ArrayOfTables(inner) = inner else {
+            return true;
+        };
+
+        inner.retain(|t| match is_needed(t, reachable.get(name)) {
+            Ok(true) => true,
+            Ok(false) => {
+                println!("{name} {}", describe(t));
+                removed += 1;
+                false
+            }
+            Err(e) => {
+                err.get_or_insert(e);
+                true
+            }
+        });
+
+        !inner.is_empty()
+    });
+    if let Some(e) = err {
+        return Err(e);
+    }
+    if atable.is_empty() {
+        atable.set_implicit(false);
+    }
+
+    if args.dry_run {
+        eprintln!("would remove {removed} audits");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    file.rewind().map_err(Error::AuditsWrite)?;
+    file.set_len(0).map_err(Error::AuditsWrite)?;
+    file.write_all(toml.to_string().as_bytes())
+        .map_err(Error::AuditsWrite)?;
+
+    eprintln!("removed {removed} audits :3");
+    Ok(ExitCode::SUCCESS)
+}
@@ -2,9 +2,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{cmp::Ordering, fmt};
+use std::{cmp::Ordering, fmt, str::FromStr};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, foxerror::FoxError)]
 pub enum Error {
@@ -42,6 +42,42 @@ pub enum Error {
     TomlBorked,
     /// the fail and base options are mutually exclusive
     FailAndBase,
+    /// notes are required, add some with -n
+    NotesRequired,
+    /// criteria implications form a cycle
+    CriteriaCycle(Cycle),
+    /// could not parse version range
+    ParseRange(String),
+    /// could not parse date, expected YYYY-MM-DD
+    ParseDate(String),
+    /// cannot edit a config read from stdin
+    ConfigNotWritable,
+    /// only one of the config and audits can be read from stdin
+    StdinTwice,
+    /// this output format is not supported here
+    UnsupportedOutput,
+    /// cannot ratchet while only checking some targets
+    RatchetWithTarget,
+    /// could not start thread pool
+    #[err(from)]
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+/// a loop of criteria that imply each other
+#[derive(Debug)]
+pub struct Cycle(pub Vec<String>);
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.0 {
+            write!(f, "{c} -> ")?;
+        }
+        // close the loop so it is obvious where it goes back to
+        if let Some(first) = self.0.first() {
+            write!(f, "{first}")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -111,6 +147,160 @@ impl Serialize for Version {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeOp {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Exact,
+}
+
+/// a set of versions, like `>=1.0.0, <1.2.4`
+///
+/// every comparison must match for a version to be in the range. a
+/// bare version only matches itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange(Vec<(RangeOp, Version)>);
+
+impl VersionRange {
+    pub fn contains(&self, version: &Version) -> bool {
+        self.0.iter().all(|(op, v)| match op {
+            RangeOp::Less => version < v,
+            RangeOp::LessEq => version <= v,
+            RangeOp::Greater => version > v,
+            RangeOp::GreaterEq => version >= v,
+            RangeOp::Exact => version == v,
+        })
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let out = s
+            .split(',')
+            .map(|part| {
+                let part = part.trim_ascii();
+                // longer operators first, so >= is not mistaken for >
+                let (op, v) = [
+                    (">=", RangeOp::GreaterEq),
+                    ("<=", RangeOp::LessEq),
+                    (">", RangeOp::Greater),
+                    ("<", RangeOp::Less),
+                    ("=", RangeOp::Exact),
+                ]
+                .into_iter()
+                .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|v| (op, v)))
+                .unwrap_or((RangeOp::Exact, part));
+                let v = v.trim_ascii();
+                if v.is_empty() {
+                    return Err(Error::ParseRange(s.to_string()));
+                }
+                Ok((op, Version::new(v)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self(out))
+    }
+}
+
+/// a calendar date, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    year: i64,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        #[allow(clippy::cast_possible_wrap)]
+        Self::from_days((secs / 86400) as i64)
+    }
+
+    /// how many days a month has, counting leap years
+    fn days_in_month(year: i64, month: u8) -> u8 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// convert days since the unix epoch into a date
+    ///
+    /// see <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn from_days(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self { year, month, day }
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::ParseDate(s.to_string());
+        let mut parts = s.trim_ascii().splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err());
+        };
+        let date = Self {
+            year: year.parse().map_err(|_| err())?,
+            month: month.parse().map_err(|_| err())?,
+            day: day.parse().map_err(|_| err())?,
+        };
+        if !(1..=12).contains(&date.month)
+            || !(1..=Self::days_in_month(date.year, date.month)).contains(&date.day)
+        {
+            return Err(err());
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 fn count_digits(s: &str) -> usize {
     s.chars().skip_while(|&c| c == '0').count()
 }
@@ -178,7 +368,42 @@ impl fmt::Display for VersionPart {
 
 #[cfg(test)]
 mod tests {
-    use super::Version;
+    use super::{Date, Version, VersionRange};
+
+    #[test]
+    fn version_range() {
+        let range: VersionRange = ">=1.0.0, <1.2.4".parse().unwrap();
+        assert!(!range.contains(&Version::new("0.9.9")));
+        assert!(range.contains(&Version::new("1.0.0")));
+        assert!(range.contains(&Version::new("1.2.3")));
+        assert!(!range.contains(&Version::new("1.2.4")));
+
+        let single: VersionRange = "1.0.2".parse().unwrap();
+        assert!(single.contains(&Version::new("1.0.2")));
+        assert!(!single.contains(&Version::new("1.0.3")));
+
+        assert!(">=1.0, ".parse::<VersionRange>().is_err());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(20_513).to_string(), "2026-03-01");
+        assert_eq!(Date::from_days(-1).to_string(), "1969-12-31");
+        assert_eq!(
+            "2025-12-31".parse::<Date>().unwrap().to_string(),
+            "2025-12-31"
+        );
+        assert!("2025-13-01".parse::<Date>().is_err());
+        assert!("2025-04-31".parse::<Date>().is_err());
+        assert!("2025-02-29".parse::<Date>().is_err());
+        assert!("2024-02-29".parse::<Date>().is_ok());
+        assert!("2000-02-29".parse::<Date>().is_ok());
+        assert!("1900-02-29".parse::<Date>().is_err());
+        assert!("2025-01-00".parse::<Date>().is_err());
+        assert!("2025-12".parse::<Date>().is_err());
+        assert!("2025-12-31".parse::<Date>().unwrap() < "2026-01-01".parse().unwrap());
+    }
 
     #[test]
     fn version_sort() {
@@ -4,7 +4,8 @@
 
 use std::{
     ffi::OsStr,
-    process::{Command, Output},
+    io::Write,
+    process::{Command, Output, Stdio},
 };
 
 static BIN: &str = env!("CARGO_BIN_EXE_vancouver");
@@ -25,6 +26,8 @@ fn test_exitcode(name: &str, output: &str, code: i32) -> String {
         &format!("{name}audits.toml"),
         "--output",
         output,
+        // a cached pass would skip the check being tested
+        "--no-cache",
     ]);
     let stdout = dbg!(str::from_utf8(&output.stdout).unwrap());
     dbg!(str::from_utf8(&output.stderr).unwrap());
@@ -37,6 +40,47 @@ fn workspace_check() {
     assert_eq!(test_exitcode(WORKSPACE, "human", 0), "");
 }
 
+#[test]
+fn cached_check() {
+    let args = [
+        "check",
+        "--manifest",
+        &format!("{WORKSPACE}Cargo.toml"),
+        "--config",
+        &format!("{WORKSPACE}vancouver.toml"),
+        "--audits",
+        &format!("{WORKSPACE}audits.toml"),
+    ];
+    // keep the cache out of the real target directory, where it could
+    // answer other tests
+    let target = std::env::temp_dir().join(format!("vancouver-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&target).unwrap();
+    let output = |extra: &[&str]| {
+        Command::new(BIN)
+            .args(args.iter().chain(extra))
+            .env("CARGO_TARGET_DIR", &target)
+            .output()
+            .unwrap()
+    };
+
+    let first = output(&[]);
+    assert!(first.status.success());
+    assert!(!str::from_utf8(&first.stderr).unwrap().contains("cached"));
+    let cached = output(&[]);
+    assert!(cached.status.success());
+    assert!(
+        str::from_utf8(&cached.stderr)
+            .unwrap()
+            .ends_with("ok (cached)\n")
+    );
+
+    let uncached = output(&["--no-cache"]);
+    assert!(uncached.status.success());
+    assert!(!str::from_utf8(&uncached.stderr).unwrap().contains("cached"));
+
+    std::fs::remove_dir_all(target).unwrap();
+}
+
 #[test]
 fn violation() {
     let stdout = test_exitcode(
@@ -46,7 +90,325 @@ fn violation() {
     );
     assert_eq!(
         stdout,
-        r#"{"dependencies":[{"fails":[{"needed":"meow","prev_version":null,"reason":"Violation"}],"name":"equivalent","status":"failed","version":"1.0.2"},{"fails":[{"needed":"meow","prev_version":"0.2.1","reason":"Violation"}],"name":"try-lock","status":"failed","version":"0.2.4"}],"total":2,"total_failed":2,"total_passed":0,"unused_exempts":[]}
+        r#"{"dependencies":[{"fails":[{"needed":"meow","prev_version":null,"reason":"Violation"}],"name":"equivalent","registry":"registry+https://github.com/rust-lang/crates.io-index","source":"registry","status":"failed","version":"1.0.2","via":["violation","equivalent"]},{"fails":[{"needed":"meow","prev_version":"0.2.1","reason":"Violation"}],"name":"try-lock","registry":"registry+https://github.com/rust-lang/crates.io-index","source":"registry","status":"failed","version":"0.2.4","via":["violation","try-lock"]}],"expired_exempts":[],"total":2,"total_failed":2,"total_passed":0,"unused_exempts":[],"unused_trusts":[]}
+"#
+    );
+}
+
+#[test]
+fn other_registry() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/violation/");
+    let output = command_output([
+        "check",
+        "--manifest",
+        &format!("{data}Cargo.toml"),
+        "--config",
+        &format!("{data}vancouver.toml"),
+        "--audits",
+        &format!("{data}audits.toml"),
+        "--registry",
+        "https://example.com/index",
+    ]);
+    // neither dependency comes from there
+    assert_eq!(output.status.code().unwrap(), 2);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn violation_sarif() {
+    let stdout = test_exitcode(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/violation/"),
+        "sarif",
+        1,
+    );
+    let sarif: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["ruleId"], "violation");
+    assert_eq!(results[1]["message"]["text"], "try-lock 0.2.4 needs meow");
+}
+
+#[test]
+fn expired_exempt() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let args = [
+        "check",
+        "--manifest",
+        &format!("{data}violation/Cargo.toml"),
+        "--config",
+        &format!("{data}expiry/vancouver.toml"),
+        "--audits",
+        &format!("{data}expiry/audits.toml"),
+    ];
+
+    let output = command_output(args.iter().chain(&["--expired"]));
+    assert_eq!(output.status.code().unwrap(), 1);
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "expired exempt: equivalent 1.0.2 meow (expired 2000-01-01)\n"
+    );
+
+    let output = command_output(args.iter().chain(&["--expired", "--output", "sarif"]));
+    assert_eq!(output.status.code().unwrap(), 2);
+    assert!(output.stdout.is_empty());
+
+    let output = command_output(args);
+    assert_eq!(output.status.code().unwrap(), 1);
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.starts_with("expired exempt: equivalent 1.0.2 meow"));
+    assert!(stdout.contains("equivalent 1.0.2\n via violation -> equivalent\n needs meow\n"));
+    assert!(!stdout.contains("try-lock"));
+}
+
+#[test]
+fn trust() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let output = command_output([
+        "check",
+        "--manifest",
+        &format!("{data}violation/Cargo.toml"),
+        "--config",
+        &format!("{data}violation/vancouver.toml"),
+        "--audits",
+        &format!("{data}trust/audits.toml"),
+    ]);
+    assert_eq!(output.status.code().unwrap(), 1);
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    // violations win over trust, leaving it unused
+    assert!(stdout.starts_with(
+        "unused trust: equivalent github:cuviper meow\nunused trust: unused github:nobody meow\n"
+    ));
+    assert!(stdout.contains(
+        "equivalent 1.0.2\n via violation -> equivalent\n needs meow\n  matched violation"
+    ));
+    assert!(!stdout.contains("try-lock"));
+}
+
+#[test]
+fn glob_patterns() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let output = command_output([
+        "check",
+        "--manifest",
+        &format!("{data}violation/Cargo.toml"),
+        "--config",
+        &format!("{data}glob/vancouver.toml"),
+        "--audits",
+        &format!("{data}expiry/audits.toml"),
+    ]);
+    assert!(output.status.success());
+    // equivalent has an exact policy, so the glob one requiring purr
+    // is ignored, and try-lock uses the more specific exemption
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "unused exempt: try* 0.2.4 meow\n"
+    );
+}
+
+#[test]
+fn require_notes() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let args = [
+        "check",
+        "--manifest",
+        &format!("{data}violation/Cargo.toml"),
+        "--config",
+        &format!("{data}violation/vancouver.toml"),
+        "--audits",
+        &format!("{data}notes/audits.toml"),
+        "--no-cache",
+    ];
+
+    assert!(command_output(args).status.success());
+
+    let output = command_output(args.iter().chain(&["--require-notes"]));
+    assert_eq!(output.status.code().unwrap(), 1);
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "missing notes: try-lock 0.2.4 meow\n"
+    );
+}
+
+#[test]
+fn json_audit_merge() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let audits = concat!(env!("CARGO_TARGET_TMPDIR"), "/json_audit_merge.toml");
+    std::fs::copy(format!("{data}notes/audits.toml"), audits).unwrap();
+
+    let output = command_output([
+        "audit", "--audits", audits, "--output", "json", "-b", "0.2.4", "-n", "tiny", "try-lock",
+        "0.2.5", "meow",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        r#"{"criteria":"meow","delta":"0.2.4 -> 0.2.5","name":"try-lock","notes":"tiny"}
 "#
     );
+
+    let merge = |file| {
+        let output = command_output([
+            "merge",
+            "--audits",
+            audits,
+            "--output",
+            "json",
+            "other",
+            &format!("{data}{file}/audits.toml"),
+        ]);
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        merge("violation"),
+        "{\"added\":5,\"removed\":0,\"unchanged\":1}\n"
+    );
+    assert_eq!(
+        merge("notes"),
+        "{\"added\":0,\"removed\":5,\"unchanged\":2}\n"
+    );
+}
+
+#[test]
+fn audit_kinds() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/violation/");
+    let audits = concat!(env!("CARGO_TARGET_TMPDIR"), "/audit_kinds.toml");
+    std::fs::write(audits, "").unwrap();
+    let audit = |args: &[&str]| command_output(["audit", "--audits", audits].iter().chain(args));
+
+    assert!(audit(&["try-lock", "0.2.1", "meow"]).status.success());
+    assert!(
+        audit(&["-b", "0.2.1", "try-lock", "0.2.4", "meow"])
+            .status
+            .success()
+    );
+    assert!(
+        audit(&["-x", "equivalent", "1.0.2", "meow"])
+            .status
+            .success()
+    );
+    let expected = r#"[[audits.try-lock]]
+version = "0.2.1"
+criteria = "meow"
+
+[[audits.try-lock]]
+delta = "0.2.1 -> 0.2.4"
+criteria = "meow"
+
+[[audits.equivalent]]
+violation = "1.0.2"
+criteria = "meow"
+"#;
+    assert_eq!(std::fs::read_to_string(audits).unwrap(), expected);
+
+    // a violation is not a delta, so both together make no sense
+    let output = audit(&["-x", "-b", "1.0.1", "equivalent", "1.0.2", "meow"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        str::from_utf8(&output.stderr)
+            .unwrap()
+            .contains("the fail and base options are mutually exclusive")
+    );
+    assert_eq!(std::fs::read_to_string(audits).unwrap(), expected);
+
+    // and check picks up the recorded violation
+    let output = command_output([
+        "check",
+        "--manifest",
+        &format!("{data}Cargo.toml"),
+        "--config",
+        &format!("{data}vancouver.toml"),
+        "--audits",
+        audits,
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.starts_with("equivalent 1.0.2\n"), "{stdout}");
+    assert!(stdout.contains("matched violation in audits file!"));
+    assert!(!stdout.contains("try-lock"));
+}
+
+#[test]
+fn config_stdin() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let mut child = Command::new(BIN)
+        .args([
+            "check",
+            "--manifest",
+            &format!("{data}violation/Cargo.toml"),
+            "--config",
+            "-",
+            "--audits",
+            &format!("{data}expiry/audits.toml"),
+            "--expired",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&std::fs::read(format!("{data}expiry/vancouver.toml")).unwrap())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code().unwrap(), 1);
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "expired exempt: equivalent 1.0.2 meow (expired 2000-01-01)\n"
+    );
+
+    let output = command_output(["check", "--config", "-", "--ratchet"]);
+    assert_eq!(output.status.code().unwrap(), 2);
+}
+
+#[test]
+fn prune_dry_run() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let output = command_output([
+        "prune",
+        "--manifest",
+        &format!("{data}violation/Cargo.toml"),
+        "--audits",
+        &format!("{data}prune/audits.toml"),
+        "--dry-run",
+    ]);
+    assert_eq!(output.status.code().unwrap(), 0);
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "try-lock 0.1.0 meow\ntry-lock 0.2.4 -> 0.2.5 meow\nunrelated 6.2.1 meow\n"
+    );
+}
+
+#[test]
+fn diff() {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/");
+    let output = command_output([
+        "diff",
+        &format!("{data}violation/audits.toml"),
+        &format!("{data}prune/audits.toml"),
+    ]);
+    assert_eq!(output.status.code().unwrap(), 1);
+    assert_eq!(
+        str::from_utf8(&output.stdout).unwrap(),
+        "equivalent
+ + violation 1.0.0 meow
+ - violation 1.0.2 meow
+try-lock
+ + 0.1.0 meow
+ + 0.2.4 -> 0.2.5 meow
+ - violation 0.2.3 meow
+unrelated
+ + 6.2.1 meow
+"
+    );
+
+    let output = command_output([
+        "diff",
+        &format!("{data}prune/audits.toml"),
+        &format!("{data}prune/audits.toml"),
+    ]);
+    assert_eq!(output.status.code().unwrap(), 0);
+    assert!(output.stdout.is_empty());
 }
@@ -2,7 +2,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::repr::{Const, Instruction, Instructions, Operand, Opnd, Opnd1, Opnd2, TwoOpnd};
+use crate::repr::{
+    Const, Instruction, Instructions, LabelOffset, Operand, Opnd, Opnd1, Opnd2, TwoOpnd,
+};
 use std::collections::BTreeMap;
 
 /// helper trait for calculating relative offsets
@@ -33,6 +35,7 @@ impl AssSize for Operand {
             | Self::Mem(_)
             | Self::AtSPn(_)
             | Self::Rel2(_)
+            | Self::LabelAddr(_)
             | Self::AtXn(_)
             | Self::AtYn(_)
             | Self::SPn(_) => 1,
@@ -98,13 +101,89 @@ impl AssSize for Instruction {
             | Self::Skeq(i)
             | Self::Sklt(i)
             | Self::Skgt(i) => 1 + i.size(),
-            Self::LabelDef(_) | Self::Comment(_) => 0,
+            // the padding depends on where it ends up, see assemble
+            Self::LabelDef(_) | Self::Comment(_) | Self::Align(_) | Self::Include(_) => 0,
             Self::Dw(v) => v.len(),
             Self::Resw(n) => *n as usize,
         }
     }
 }
 
+/// helper trait for finding the relative addresses in an instruction
+trait AssRel {
+    fn rels(&self) -> Vec<&LabelOffset>;
+}
+
+impl AssRel for Operand {
+    fn rels(&self) -> Vec<&LabelOffset> {
+        if let Self::Rel2(rel) = self {
+            vec![rel]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl<T> AssRel for Opnd<T> {
+    fn rels(&self) -> Vec<&LabelOffset> {
+        self.left().value().rels()
+    }
+}
+
+impl<L, R> AssRel for TwoOpnd<L, R> {
+    fn rels(&self) -> Vec<&LabelOffset> {
+        let mut rels = self.left().value().rels();
+        rels.extend(self.right().value().rels());
+        rels
+    }
+}
+
+impl AssRel for Instruction {
+    fn rels(&self) -> Vec<&LabelOffset> {
+        match self {
+            Self::Jump(i) | Self::Call(i) | Self::Push(i) => i.rels(),
+            Self::Inc(i) | Self::Dec(i) | Self::Not(i) | Self::Pop(i) | Self::Swap(i) => i.rels(),
+            Self::Xchg(i) => i.rels(),
+            Self::Move(i)
+            | Self::Add(i)
+            | Self::Sub(i)
+            | Self::Mul(i)
+            | Self::Div(i)
+            | Self::And(i)
+            | Self::Or(i)
+            | Self::Xor(i)
+            | Self::In(i)
+            | Self::Dbnz(i)
+            | Self::Mod(i)
+            | Self::Shl(i)
+            | Self::Shr(i)
+            | Self::Addc(i)
+            | Self::Mulc(i)
+            | Self::Msb(i) => i.rels(),
+            Self::Bnze(i)
+            | Self::Bze(i)
+            | Self::Bpos(i)
+            | Self::Bneg(i)
+            | Self::Out(i)
+            | Self::Skne(i)
+            | Self::Skeq(i)
+            | Self::Sklt(i)
+            | Self::Skgt(i) => i.rels(),
+            Self::Nop
+            | Self::Ret
+            | Self::Halt
+            | Self::Brk(_)
+            | Self::Sys(_)
+            | Self::LabelDef(_)
+            | Self::Comment(_)
+            | Self::Dw(_)
+            | Self::Resw(_)
+            | Self::Align(_)
+            | Self::Include(_) => vec![],
+        }
+    }
+}
+
 #[derive(Debug, foxerror::FoxError)]
 pub enum Error {
     /// skip to middle of instruction likely incorrect
@@ -121,14 +200,48 @@ pub enum Error {
     CodeTooLong,
     /// could not find label
     LabelNotFound(String),
+    /// relative address points too far away or outside of memory
+    BranchOutOfRange {
+        instruction: Box<Instruction>,
+        target: LabelOffset,
+        distance: i32,
+    },
 }
 
 fn label_offset(label: &str, loc: u16, labels: &BTreeMap<String, u16>) -> Result<u16, Error> {
-    if let Some(l) = labels.get(label) {
-        return Ok(l.wrapping_sub(loc));
-    }
+    Ok(label_addr(label, labels)?.wrapping_sub(loc))
+}
 
-    Err(Error::LabelNotFound(label.to_string()))
+fn label_addr(label: &str, labels: &BTreeMap<String, u16>) -> Result<u16, Error> {
+    labels
+        .get(label)
+        .copied()
+        .ok_or_else(|| Error::LabelNotFound(label.to_string()))
+}
+
+/// check that the relative addresses in an instruction point no more
+/// than `limit` words away, without wrapping around the ends of memory
+fn check_branches(
+    loc: u16,
+    instruction: &Instruction,
+    labels: &BTreeMap<String, u16>,
+    limit: u16,
+) -> Result<(), Error> {
+    for rel in instruction.rels() {
+        let base = rel
+            .name()
+            .map_or(Ok(loc), |label| label_addr(label, labels))?;
+        let target = i32::from(base) + i32::from(rel.offset().value());
+        let distance = target - i32::from(loc);
+        if u16::try_from(target).is_err() || distance.unsigned_abs() > u32::from(limit) {
+            return Err(Error::BranchOutOfRange {
+                instruction: Box::new(instruction.clone()),
+                target: rel.clone(),
+                distance,
+            });
+        }
+    }
+    Ok(())
 }
 
 enum Extra {
@@ -168,6 +281,14 @@ fn extra(operand: &Operand, loc: u16, labels: &BTreeMap<String, u16>) -> Result<
             }
             .wrapping_add_signed(rel.offset().value()),
         ),
+        Operand::LabelAddr(addr) => Extra::One(
+            if let Some(label) = addr.name() {
+                label_addr(label, labels)?
+            } else {
+                0
+            }
+            .wrapping_add_signed(addr.offset().value()),
+        ),
     })
 }
 
@@ -193,7 +314,7 @@ impl AssPart for Opnd1 {
             Operand::AtYInc => 0x0160,
             Operand::Immed0 => 0x0180,
             Operand::Immed1 => 0x01a0,
-            Operand::Immediate(_) => 0x0200,
+            Operand::Immediate(_) | Operand::LabelAddr(_) => 0x0200,
             Operand::Mem(_) => 0x0220,
             Operand::AtSPn(_) => 0x0260,
             Operand::Rel2(_) => 0x0280,
@@ -223,7 +344,7 @@ impl AssPart for Opnd2 {
             Operand::AtYInc => 0x000b,
             Operand::Immed0 => 0x000c,
             Operand::Immed1 => 0x000d,
-            Operand::Immediate(_) => 0x0010,
+            Operand::Immediate(_) | Operand::LabelAddr(_) => 0x0010,
             Operand::Mem(_) => 0x0011,
             Operand::AtSPn(_) => 0x0013,
             Operand::Rel2(_) => 0x0014,
@@ -314,7 +435,12 @@ fn assemble_one(
         Instruction::Sklt(o) => opart!(0x9400, o),
         Instruction::Skgt(o) => opart!(0x9800, o),
         Instruction::Msb(o) => opart!(0x9c00, o),
-        Instruction::LabelDef(_) | Instruction::Comment(_) => return Ok(vec![]),
+        Instruction::LabelDef(_)
+        | Instruction::Comment(_)
+        | Instruction::Align(_)
+        | Instruction::Include(_) => {
+            return Ok(vec![]);
+        }
         Instruction::Dw(v) => return Ok(v.clone()),
         Instruction::Resw(n) => return Ok(vec![0; *n as usize]),
     };
@@ -376,12 +502,51 @@ impl SkChecker {
 }
 
 pub fn assemble(rep: Instructions) -> Result<Vec<u16>, Error> {
+    Ok(assemble_with_labels(rep, 0, None)?.0)
+}
+
+/// like [`assemble`], but for code starting at `origin`, and also gives
+/// back where each label ended up
+///
+/// the origin only matters for absolute label addresses, relative ones
+/// come out the same wherever the code is
+///
+/// relative addresses are allowed to wrap around memory, unless a
+/// `branch_limit` is given. then they must stay within memory and no
+/// further than that many words from the instruction using them
+pub fn assemble_with_labels(
+    rep: Instructions,
+    origin: u16,
+    branch_limit: Option<u16>,
+) -> Result<(Vec<u16>, BTreeMap<String, u16>), Error> {
     let mut labels = BTreeMap::new();
     let mut loc = rep
         .0
         .into_iter()
         .scan((0u16, SkChecker::None), |(statepos, skt), i| {
             let pos = *statepos;
+            if let Instruction::Align(n) = i {
+                // alignment is of the address, which only matches pos at origin 0
+                let addr = u32::from(origin.wrapping_add(pos));
+                let Some(new) = addr
+                    .checked_next_multiple_of(n.into())
+                    .and_then(|a| u16::try_from(a - addr).ok())
+                    .and_then(|pad| pos.checked_add(pad))
+                else {
+                    return Some(Err(Error::CodeTooLong));
+                };
+                // a sk* right before would skip the padding instead of the
+                // instruction after it. once the skipped instruction has
+                // started, landing on a zero nop is harmless
+                if new > pos && matches!(skt, SkChecker::Two | SkChecker::OneOne) {
+                    return Some(Err(Error::SkMistake(i)));
+                }
+                *statepos = new;
+                for _ in pos..new {
+                    skt.advance(1, false);
+                }
+                return Some(Ok((pos, Instruction::Resw(new - pos))));
+            }
             let Ok(size) = u16::try_from(i.size()) else {
                 return Some(Err(Error::InstructionTooBig(i)));
             };
@@ -394,7 +559,9 @@ pub fn assemble(rep: Instructions) -> Result<Vec<u16>, Error> {
                 return Some(Err(Error::SkMistake(i)));
             }
             if let Instruction::LabelDef(ref def) = i
-                && labels.insert(def.clone(), pos).is_some()
+                && labels
+                    .insert(def.clone(), origin.wrapping_add(pos))
+                    .is_some()
             {
                 return Some(Err(Error::DuplicateLabel(def.clone())));
             }
@@ -419,8 +586,11 @@ pub fn assemble(rep: Instructions) -> Result<Vec<u16>, Error> {
             "instruction before {ins} has incorrect size"
         );
 
-        out.append(&mut assemble_one(l, &ins, &labels)?);
+        if let Some(limit) = branch_limit {
+            check_branches(origin.wrapping_add(l), &ins, &labels, limit)?;
+        }
+        out.append(&mut assemble_one(origin.wrapping_add(l), &ins, &labels)?);
     }
 
-    Ok(out)
+    Ok((out, labels))
 }
@@ -8,7 +8,7 @@ use argh::{FromArgs, from_env};
 use std::{
     fs::File,
     io::{BufReader, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::ExitCode,
 };
 
@@ -20,6 +20,7 @@ mod repr;
 
 /// vulpine's vm16 assembler
 #[derive(Debug, FromArgs)]
+#[expect(clippy::struct_excessive_bools)]
 #[argh(help_triggers("-h", "--help"))]
 struct Opt {
     /// where to send assembled output (dumps hex to stdout by default)
@@ -33,10 +34,47 @@ struct Opt {
     /// disassemble instead of assembling
     #[argh(switch, short = 'd')]
     disassemble: bool,
+    /// check that disassembling and reassembling the machine code
+    /// gives back the exact same words
+    #[argh(switch)]
+    verify: bool,
+    /// print each label and its address to stderr, sorted by address
+    #[argh(switch)]
+    symbols: bool,
+    /// error on relative addresses that wrap around memory or point
+    /// further than --branch-limit away
+    #[argh(switch)]
+    strict_branches: bool,
+    /// how many words away relative addresses may point with
+    /// --strict-branches (default 32767)
+    #[argh(option, default = "0x7fff")]
+    branch_limit: u16,
+    /// base to dump words to stdout in: hex, dec, or bin
+    #[argh(option, default = "Base::Hex")]
+    base: Base,
     #[argh(positional)]
     file: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Base {
+    Hex,
+    Dec,
+    Bin,
+}
+
+impl std::str::FromStr for Base {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "hex" => Self::Hex,
+            "dec" => Self::Dec,
+            "bin" => Self::Bin,
+            _ => return Err("base should be hex, dec, or bin"),
+        })
+    }
+}
+
 #[derive(Debug, foxerror::FoxError)]
 enum Error {
     /// io error
@@ -54,12 +92,79 @@ enum Error {
     /// parse int error
     #[err(from)]
     ParseInt(std::num::ParseIntError),
+    /// reassembled output first differs at address
+    VerifyMismatch(String),
+    /// in included file
+    Included(String, Box<Self>),
+    /// include cycle
+    IncludeCycle,
 }
 
 fn parse_hex16(inp: &str) -> Result<u16, String> {
     u16::from_str_radix(inp, 16).map_err(|e| e.to_string())
 }
 
+/// parse some assembly, splicing in the files it includes
+///
+/// `stack` holds the files currently being included, to catch cycles
+fn parse_including(
+    input: &str,
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Vec<repr::Instruction>, Error> {
+    let mut out = vec![];
+    for i in parse::parse(input)?.0 {
+        let repr::Instruction::Include(name) = i else {
+            out.push(i);
+            continue;
+        };
+        let path = dir.join(name);
+        let included = include(&path, stack)
+            .map_err(|e| Error::Included(path.display().to_string(), Box::new(e)))?;
+        out.extend(included);
+    }
+    Ok(out)
+}
+
+fn include(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Vec<repr::Instruction>, Error> {
+    let canon = path.canonicalize()?;
+    if stack.contains(&canon) {
+        return Err(Error::IncludeCycle);
+    }
+    let input = std::fs::read_to_string(&canon)?;
+    stack.push(canon);
+    let out = parse_including(
+        &input,
+        path.parent().unwrap_or_else(|| Path::new("")),
+        stack,
+    );
+    stack.pop();
+    out
+}
+
+/// disassemble and reassemble some machine code, returning the index
+/// of the first word that does not survive the round trip
+fn first_divergence(words: &[u16]) -> Result<Option<usize>, Error> {
+    let reassembled = assemble::assemble(disassemble::disassemble(words))?;
+
+    let common = words.len().min(reassembled.len());
+    let pos = words.iter().zip(&reassembled).position(|(l, r)| l != r);
+
+    Ok(pos.or_else(|| (words.len() != reassembled.len()).then_some(common)))
+}
+
+fn verify(opt: &Opt, words: &[u16]) -> Result<(), Error> {
+    if !opt.verify {
+        return Ok(());
+    }
+    if let Some(pos) = first_divergence(words)? {
+        #[allow(clippy::cast_possible_truncation)]
+        let addr = opt.h16.unwrap_or(0).wrapping_add(pos as u16);
+        return Err(Error::VerifyMismatch(format!("{addr:#06x}")));
+    }
+    Ok(())
+}
+
 fn run(opt: &Opt) -> Result<(), Error> {
     if opt.disassemble {
         let bytes = if let Some(start) = opt.h16 {
@@ -89,6 +194,7 @@ fn run(opt: &Opt) -> Result<(), Error> {
                 })
                 .collect::<Result<Vec<_>, _>>()?
         };
+        verify(opt, &bytes)?;
         let disassembled = disassemble::disassemble(&bytes);
         if let Some(output) = &opt.output {
             let mut file = File::create(output)?;
@@ -99,12 +205,36 @@ fn run(opt: &Opt) -> Result<(), Error> {
         return Ok(());
     }
 
-    let input = if let Some(file) = &opt.file {
-        std::fs::read_to_string(file)?
+    let (input, dir, mut stack) = if let Some(file) = &opt.file {
+        (
+            std::fs::read_to_string(file)?,
+            file.parent().unwrap_or_else(|| Path::new("")),
+            file.canonicalize().into_iter().collect(),
+        )
     } else {
-        std::io::read_to_string(std::io::stdin())?
+        (
+            std::io::read_to_string(std::io::stdin())?,
+            Path::new(""),
+            vec![],
+        )
     };
-    let assembled = assemble::assemble(parse::parse(&input)?)?;
+    let instructions = parse_including(&input, dir, &mut stack)?;
+    let (assembled, labels) = assemble::assemble_with_labels(
+        repr::Instructions(instructions),
+        opt.h16.unwrap_or(0),
+        opt.strict_branches.then_some(opt.branch_limit),
+    )?;
+    verify(opt, &assembled)?;
+    if opt.symbols {
+        let mut labels: Vec<_> = labels
+            .into_iter()
+            .map(|(name, addr)| (addr, name))
+            .collect();
+        labels.sort();
+        for (addr, name) in labels {
+            eprintln!("{addr:04x} {name}");
+        }
+    }
 
     if let Some(output) = &opt.output {
         let mut file = File::create(output)?;
@@ -118,7 +248,11 @@ fn run(opt: &Opt) -> Result<(), Error> {
         print!("{}", h16::H16Display::new(start, &assembled));
     } else {
         for word in assembled {
-            println!("{word:x}");
+            match opt.base {
+                Base::Hex => println!("{word:04x}"),
+                Base::Dec => println!("{word}"),
+                Base::Bin => println!("{word:016b}"),
+            }
         }
     }
 
@@ -6,11 +6,14 @@ use crate::repr::{
     self, Const, Dst, Instruction, Instructions, LabelOffset, Operand, Opnd, Src, TwoOpnd,
 };
 use nom::{
-    Err, IResult, Parser,
+    Err, Parser,
     branch::alt,
     bytes::complete::{is_not, tag},
-    character::complete::{alpha1, alphanumeric1, anychar, multispace0, one_of, space0, space1},
-    combinator::{complete, map, map_res, opt, recognize, value},
+    character::complete::{
+        alpha1, alphanumeric1, anychar, multispace0, none_of, one_of, space0, space1,
+    },
+    combinator::{complete, map, map_res, opt, peek, recognize, value, verify},
+    error::{ErrorKind, FromExternalError, ParseError},
     multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated},
 };
@@ -76,52 +79,222 @@ pub enum Error {
     /// invalid syntax or value
     #[err(from)]
     Parse(LineContext),
+    /// arithmetic overflowed
+    Overflow(LineContext),
+    /// number does not fit in 16 bits
+    TooBig(LineContext),
+    /// division by zero
+    DivideByZero(LineContext),
+}
+
+/// why some constant arithmetic could not be evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExprError {
+    Overflow,
+    DivideByZero,
+    TooBig,
+}
+
+/// nom's default error, but able to tell apart arithmetic gone wrong
+/// from plain old invalid syntax
+#[derive(Debug, PartialEq, Eq)]
+struct PError<I> {
+    input: I,
+    expr: Option<ExprError>,
+}
+
+impl<I> PError<I> {
+    fn expr(input: I, expr: ExprError) -> Err<Self> {
+        Err::Failure(Self {
+            input,
+            expr: Some(expr),
+        })
+    }
+}
+
+impl<I> ParseError<I> for PError<I> {
+    fn from_error_kind(input: I, _: ErrorKind) -> Self {
+        Self { input, expr: None }
+    }
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for PError<I> {
+    fn from_external_error(input: I, kind: ErrorKind, _: E) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
+
+type IResult<I, O> = nom::IResult<I, O, PError<I>>;
+
+/// the digits are already known to be valid, so the only way parsing
+/// them can fail is by being too big
+fn digits_value<'a>(inp: &'a str, digits: &str, radix: u32) -> Result<u16, Err<PError<&'a str>>> {
+    u16::from_str_radix(digits, radix).map_err(|_| PError::expr(inp, ExprError::TooBig))
 }
 
 fn hexadecimal_value(inp: &str) -> IResult<&str, u16> {
-    map_res(
-        preceded(
-            alt((tag("$"), tag("0x"), tag("0X"))),
-            recognize(many1(one_of("0123456789abcdefABCDEF"))),
-        ),
-        |out| u16::from_str_radix(out, 16),
+    let (rest, digits) = preceded(
+        alt((tag("$"), tag("0x"), tag("0X"))),
+        recognize(many1(one_of("0123456789abcdefABCDEF"))),
     )
-    .parse(inp)
+    .parse(inp)?;
+    Ok((rest, digits_value(inp, digits, 16)?))
 }
 
 fn decimal_value(inp: &str) -> IResult<&str, u16> {
-    map_res(recognize(many1(one_of("0123456789"))), |out: &str| {
-        out.parse::<u16>()
-    })
-    .parse(inp)
+    let (rest, digits) = recognize(many1(one_of("0123456789"))).parse(inp)?;
+    Ok((rest, digits_value(inp, digits, 10)?))
 }
 
 fn char_value(inp: &str) -> IResult<&str, u16> {
-    map(delimited(tag("'"), anychar, tag("'")), |out| out as u16).parse(inp)
+    let ascii = || verify(anychar, char::is_ascii);
+    alt((
+        // two characters get packed into one word like c"ab"
+        map(
+            delimited(tag("'"), (ascii(), ascii()), tag("'")),
+            |(a, b)| ((a as u16) << 8) + b as u16,
+        ),
+        map(delimited(tag("'"), anychar, tag("'")), |out| out as u16),
+    ))
+    .parse(inp)
 }
 
-fn number_value(inp: &str) -> IResult<&str, u16> {
+fn literal_value(inp: &str) -> IResult<&str, u16> {
     alt((hexadecimal_value, decimal_value, char_value)).parse(inp)
 }
 
+/// wider than any operand, so that the steps in between may go out of
+/// range as long as the end result fits
+type Value = i32;
+
+fn apply(start: &str, left: Value, op: char, right: Value) -> Result<Value, Err<PError<&str>>> {
+    match op {
+        '+' => left.checked_add(right),
+        '-' => left.checked_sub(right),
+        '*' => left.checked_mul(right),
+        '/' if right == 0 => return Err(PError::expr(start, ExprError::DivideByZero)),
+        '/' => left.checked_div(right),
+        _ => unreachable!("only called with operators from one_of"),
+    }
+    .ok_or_else(|| PError::expr(start, ExprError::Overflow))
+}
+
+/// left fold the rest of a chain of operators with the same precedence
+fn chain<'a>(
+    start: &'a str,
+    mut inp: &'a str,
+    mut acc: Value,
+    ops: &'static str,
+    operand: fn(&str) -> IResult<&str, Value>,
+) -> IResult<&'a str, Value> {
+    loop {
+        match pair(delimited(space0, one_of(ops), space0), operand).parse(inp) {
+            Ok((rest, (op, right))) => {
+                acc = apply(start, acc, op, right)?;
+                inp = rest;
+            }
+            Err(Err::Error(_)) => return Ok((inp, acc)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn factor(inp: &str) -> IResult<&str, Value> {
+    if let Some(rest) = inp.strip_prefix('-') {
+        let (rest, value) = factor(rest)?;
+        let value = value
+            .checked_neg()
+            .ok_or_else(|| PError::expr(inp, ExprError::Overflow))?;
+        return Ok((rest, value));
+    }
+    alt((
+        map(literal_value, Value::from),
+        delimited(pair(tag("("), space0), expression, pair(space0, tag(")"))),
+    ))
+    .parse(inp)
+}
+
+fn term(inp: &str) -> IResult<&str, Value> {
+    let (rest, first) = factor(inp)?;
+    chain(inp, rest, first, "*/", factor)
+}
+
+fn expression(inp: &str) -> IResult<&str, Value> {
+    let (rest, first) = term(inp)?;
+    chain(inp, rest, first, "+-", term)
+}
+
+/// a number that fits in a word, with negative numbers becoming their
+/// two's complement
+fn number_value(inp: &str) -> IResult<&str, u16> {
+    let (rest, value) = expression(inp)?;
+    let value = u16::try_from(value)
+        .or_else(|_| i16::try_from(value).map(i16::cast_unsigned))
+        .map_err(|_| PError::expr(inp, ExprError::Overflow))?;
+    Ok((rest, value))
+}
+
 #[test]
 fn test_numbers() {
     assert_eq!(number_value("1234"), Ok(("", 1234)));
     assert_eq!(number_value("0xaaaa"), Ok(("", 0xaaaa)));
+    assert_eq!(number_value("2+3*4"), Ok(("", 14)));
+    assert_eq!(number_value("(2 + 3) * 4"), Ok(("", 20)));
+    assert_eq!(number_value("1-2+3"), Ok(("", 2)));
+    assert_eq!(number_value("10/3"), Ok(("", 3)));
+    assert_eq!(number_value("'a'+1"), Ok(("", 98)));
+    assert_eq!(number_value("'''"), Ok(("", 39)));
+    assert_eq!(number_value("'ab'"), Ok(("", 0x6162)));
+    assert_eq!(
+        number_value("'ab'").unwrap().1,
+        string_packed("c\"ab\"").unwrap().1[0]
+    );
+    assert!(number_value("'abc'").is_err());
+    assert_eq!(number_value("4 nop"), Ok((" nop", 4)));
+    assert_eq!(number_value("-1"), Ok(("", 0xffff)));
+    assert_eq!(number_value("2*-3"), Ok(("", 0xfffa)));
+    assert_eq!(number_value("-32768"), Ok(("", 0x8000)));
+    assert_eq!(
+        number_value("-32769"),
+        Err(PError::expr("-32769", ExprError::Overflow))
+    );
+    assert_eq!(
+        number_value("65535+1"),
+        Err(PError::expr("65535+1", ExprError::Overflow))
+    );
+    assert_eq!(
+        number_value("1+2/0"),
+        Err(PError::expr("2/0", ExprError::DivideByZero))
+    );
+    assert_eq!(
+        number_value("0x1ffff"),
+        Err(PError::expr("0x1ffff", ExprError::TooBig))
+    );
+    assert_eq!(
+        number_value("1+65536"),
+        Err(PError::expr("65536", ExprError::TooBig))
+    );
+    assert_eq!(
+        number_offset("+$10000"),
+        Err(PError::expr("$10000", ExprError::TooBig))
+    );
 }
 
 fn number_offset(inp: &str) -> IResult<&str, repr::Offset> {
-    map_res(
-        pair(
-            alt((value(false, tag("+")), value(true, tag("-")))),
-            number_value,
-        ),
-        |(neg, num)| {
-            num.try_into()
-                .map(|n| repr::Offset::new(if neg { 0 - n } else { n }))
-        },
-    )
-    .parse(inp)
+    let (rest, (neg, first)) =
+        pair(alt((value(false, tag("+")), value(true, tag("-")))), term).parse(inp)?;
+    let overflow = || PError::expr(inp, ExprError::Overflow);
+    let first = if neg {
+        first.checked_neg().ok_or_else(overflow)?
+    } else {
+        first
+    };
+    let (rest, value) = chain(inp, rest, first, "+-", term)?;
+    let value = i16::try_from(value).map_err(|_| overflow())?;
+    Ok((rest, repr::Offset::new(value)))
 }
 
 fn label_name(inp: &str) -> IResult<&str, &str> {
@@ -168,6 +341,17 @@ fn test_label_offset() {
             LabelOffset::new(Some("meow".to_string()), repr::Offset::new(-3456))
         ))
     );
+    assert_eq!(
+        label_offset("meow+2*8"),
+        Ok((
+            "",
+            LabelOffset::new(Some("meow".to_string()), repr::Offset::new(16))
+        ))
+    );
+    assert_eq!(
+        label_offset("-2+1"),
+        Ok(("", LabelOffset::new(None, repr::Offset::new(-1))))
+    );
     assert_eq!(
         label_offset("meow"),
         Ok((
@@ -183,7 +367,11 @@ fn operand(inp: &str) -> IResult<&str, Operand> {
         value(Operand::AtY, tag("[Y]")),
         value(Operand::AtXInc, tag("[X++]")),
         value(Operand::AtYInc, tag("[Y++]")),
-        map(number_value, Operand::new_immediate),
+        // a leading sign is a relative address instead
+        map(
+            preceded(peek(none_of("+-")), number_value),
+            Operand::new_immediate,
+        ),
         map(delimited(tag("["), number_value, tag("]")), |v| {
             Operand::Mem(repr::MemoryAddress::new(v))
         }),
@@ -194,6 +382,15 @@ fn operand(inp: &str) -> IResult<&str, Operand> {
         map(delimited(tag("[X"), number_offset, tag("]")), Operand::AtXn),
         map(delimited(tag("[Y"), number_offset, tag("]")), Operand::AtYn),
         map(preceded(tag("SP"), number_offset), Operand::SPn),
+        map(
+            preceded(tag("#"), pair(label_name, opt(number_offset))),
+            |(name, offset)| {
+                Operand::LabelAddr(LabelOffset::new(
+                    Some(name.to_string()),
+                    offset.unwrap_or_else(|| repr::Offset::new(0)),
+                ))
+            },
+        ),
         value(Operand::A, tag("A")),
         value(Operand::B, tag("B")),
         value(Operand::C, tag("C")),
@@ -277,10 +474,40 @@ fn test_operands() {
             )
         ))
     );
+    assert_eq!(
+        two_opnd(" A, (-1)"),
+        Ok((
+            "",
+            (
+                Operand::A,
+                Operand::Immediate(repr::Immediate::new(0xffff).unwrap())
+            )
+        ))
+    );
+    assert_eq!(
+        one_opnd(" -1"),
+        Ok((
+            "",
+            Operand::Rel2(LabelOffset::new(None, repr::Offset::new(-1)))
+        ))
+    );
     assert_eq!(
         one_opnd(" SP-69"),
         Ok(("", Operand::SPn(repr::Offset::new(-69)))),
     );
+    assert_eq!(
+        two_opnd(" X, #table+2"),
+        Ok((
+            "",
+            (
+                Operand::X,
+                Operand::LabelAddr(LabelOffset::new(
+                    Some("table".to_string()),
+                    repr::Offset::new(2)
+                ))
+            )
+        ))
+    );
 }
 
 fn string_value(inp: &str) -> IResult<&str, Vec<u16>> {
@@ -400,6 +627,16 @@ fn instruction(inp: &str) -> IResult<&str, Instruction> {
         ),
         alts!(Dst, Dst, ((Xchg, "xchg"))),
         alt((
+            map(
+                preceded(
+                    tag("dwz"),
+                    preceded(
+                        space1,
+                        separated_list1(tag(","), delimited(space0, number_words, space0)),
+                    ),
+                ),
+                |v| Instruction::Dw(v.into_iter().flatten().chain([0]).collect()),
+            ),
             map(
                 preceded(
                     tag("dw"),
@@ -414,6 +651,24 @@ fn instruction(inp: &str) -> IResult<&str, Instruction> {
                 preceded(tag("resw"), delimited(space1, number_value, space0)),
                 Instruction::Resw,
             ),
+            map(
+                preceded(
+                    tag("align"),
+                    delimited(space1, verify(number_value, |&n| n > 0), space0),
+                ),
+                Instruction::Align,
+            ),
+            map(
+                preceded(
+                    tag("include"),
+                    delimited(
+                        space1,
+                        delimited(tag("\""), is_not("\""), tag("\"")),
+                        space0,
+                    ),
+                ),
+                |f: &str| Instruction::Include(f.to_string()),
+            ),
         )),
     ))
     .parse(inp)
@@ -496,7 +751,13 @@ fn test_instructions() {
     ins!("dw 1", Dw(vec![1]));
     ins!("dw \"meow\", 0", Dw(vec![109, 101, 111, 119, 0]));
     ins!("dw c\"mow\", 0", Dw(vec![0x6d6f, 0x77, 0]));
+    ins!("dw -1, -2", Dw(vec![0xffff, 0xfffe]));
+    ins!("dwz \"meow\"", Dw(vec![109, 101, 111, 119, 0]));
+    ins!("dwz c\"mow\", 'a'", Dw(vec![0x6d6f, 0x77, 0x61, 0]));
     ins!("resw 6", Resw(6));
+    ins!("align 4", Align(4));
+    ins!("include \"lib.asm\"", Include("lib.asm".to_string()));
+    assert!(instruction("align 0").is_err());
 }
 
 fn comment(inp: &str) -> IResult<&str, Instruction> {
@@ -524,7 +785,13 @@ pub fn parse(inp: &str) -> Result<Instructions, Error> {
             Err::Error(i) | Err::Failure(i) => i,
             Err::Incomplete(_) => unreachable!("complete should turn this into Err::Error"),
         };
-        LineContext::get_context(inp, inner.input)
+        let context = LineContext::get_context(inp, inner.input);
+        match inner.expr {
+            Some(ExprError::Overflow) => Error::Overflow(context),
+            Some(ExprError::DivideByZero) => Error::DivideByZero(context),
+            Some(ExprError::TooBig) => Error::TooBig(context),
+            None => Error::Parse(context),
+        }
     })?;
     // TODO: replace with nom-supreme's final_parser once it supports nom v8
     // it'll also get less useless error messages and map_res_cut
@@ -147,6 +147,10 @@ pub enum Operand {
     ///
     /// takes an extra word
     ///
+    /// negative values get stored as their two's complement, but need
+    /// to be wrapped in parentheses like `(-1)`, since a leading sign
+    /// would make it a relative address
+    ///
     /// equivalent to the in game assembler's `#n`
     Immediate(Immediate),
     /// `[n]` value at address
@@ -163,6 +167,15 @@ pub enum Operand {
     ///
     /// takes an extra word
     Rel2(LabelOffset),
+    /// `#label`/`#label+n` absolute address of a label, as an immediate
+    ///
+    /// takes an extra word
+    ///
+    /// unlike the relative form, which works out the address from where
+    /// the instruction is at runtime, this is fixed when assembling based
+    /// on the starting position. code using it only works when loaded
+    /// at that position
+    LabelAddr(LabelOffset),
     /// `[X+n]` value at address `X` plus offset
     ///
     /// takes an extra word
@@ -188,7 +201,7 @@ impl Operand {
     fn is_src_exclusive(&self) -> bool {
         matches!(
             self,
-            Self::Immed0 | Self::Immed1 | Self::Immediate(_) | Self::Rel2(_)
+            Self::Immed0 | Self::Immed1 | Self::Immediate(_) | Self::Rel2(_) | Self::LabelAddr(_)
         )
     }
 }
@@ -217,6 +230,7 @@ impl fmt::Display for Operand {
                 Self::Mem(i) => return write!(f, "[{i}]"),
                 Self::AtSPn(i) => return write!(f, "[SP{i}]"),
                 Self::Rel2(i) => return write!(f, "{i}"),
+                Self::LabelAddr(i) => return write!(f, "#{i}"),
                 Self::AtXn(i) => return write!(f, "[X{i}]"),
                 Self::AtYn(i) => return write!(f, "[Y{i}]"),
                 Self::SPn(i) => return write!(f, "SP{i}"),
@@ -494,6 +508,20 @@ pub enum Instruction {
     /// not a real opcode, may output zeros or leave what was there
     /// previously untouched
     Resw(u16),
+    /// pad with zeros until the location is a multiple of a number of
+    /// words
+    ///
+    /// not a real opcode, the padding is made of `nop`s so it is safe
+    /// to be skipped into
+    Align(u16),
+    /// splice in the instructions of another file, relative to the
+    /// directory of the current one
+    /// ```custom
+    /// include "lib.asm"
+    /// ```
+    ///
+    /// not a real opcode, gets replaced before assembling
+    Include(String),
 }
 
 impl Instruction {
@@ -568,6 +596,8 @@ impl fmt::Display for Instruction {
                 Ok(())
             }
             Self::Resw(o) => w!("resw {}", o),
+            Self::Align(o) => w!("align {}", o),
+            Self::Include(o) => w!("include \"{}\"", o),
         }
     }
 }
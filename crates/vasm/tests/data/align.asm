@@ -0,0 +1,13 @@
+; pad things out so they land on nice boundaries
+start:
+	move A, 0x1234
+	align 4
+table:
+	dw 1, 2, 3
+	align 8
+code:
+	skne A, B
+	jump start
+	align 4
+	halt
+	align 2
@@ -0,0 +1,7 @@
+; alignment is of the address, not the offset into the code
+	dw 1
+	align 4
+here:
+	move A, #here
+	align 8
+	halt
@@ -0,0 +1,5 @@
+; labels from included files are global
+start:
+	call print
+	jump start
+include "include/print.asm"
@@ -0,0 +1,2 @@
+	nop
+include "cycle2.asm"
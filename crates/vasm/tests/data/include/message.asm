@@ -0,0 +1,2 @@
+message:
+	dw "hi", 0
@@ -0,0 +1,4 @@
+print:
+	move X, message
+	ret
+include "message.asm"
@@ -0,0 +1,10 @@
+; absolute label addresses as immediates
+start:
+	move X, #table
+	move Y, #table+1
+	move A, [X]
+	add A, [Y]
+	move [X+2], A
+	halt
+table:
+	dw 2, 3, 0
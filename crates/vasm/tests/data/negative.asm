@@ -0,0 +1,6 @@
+; negative numbers are two's complement
+	move A, (-1)
+	add B, 2*-3
+	move [X-2], (-32768)
+	jump -6
+	dw -1, -0x10
@@ -74,12 +74,31 @@ fn roundtrip(name: &str, h: u16) {
     assert_eq!(lines.next(), None);
 }
 
+fn verify(name: &str, h: u16) {
+    let testname = Path::new(DATA_DIR).join(name);
+    let output = Command::new(BIN)
+        .arg("--verify")
+        .arg("--h16")
+        .arg(format!("{h:x}"))
+        .arg(testname.with_extension("asm"))
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stderr.is_empty());
+}
+
 macro_rules! snap {
     ($name:ident, $offset:expr) => {
         #[test]
         fn $name() {
             snapshot(stringify!($name), $offset);
             roundtrip(stringify!($name), $offset);
+            verify(stringify!($name), $offset);
         }
     };
     ($name:ident) => {
@@ -93,7 +112,12 @@ snap!(chal3);
 snap!(chal4);
 snap!(chal5);
 
+snap!(align);
+snap!(alignorigin, 2);
 snap!(hwrite);
+snap!(include);
+snap!(labeladdr, 0x100);
+snap!(negative);
 snap!(uninit, 0xfffe);
 
 #[test]
@@ -122,3 +146,134 @@ fn dis_odd() {
         "\tdw 0x6d65\n\tdw 0x6f77\n\tpush A\n"
     );
 }
+
+#[test]
+fn include_cycle() {
+    let output = Command::new(BIN)
+        .arg(Path::new(DATA_DIR).join("include/cycle.asm"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .ends_with("cycle.asm, include cycle\n")
+    );
+}
+
+#[test]
+fn symbols() {
+    let output = Command::new(BIN)
+        .args(["--symbols", "--h16", "100"])
+        .arg(Path::new(DATA_DIR).join("include.asm"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "0100 start\n0104 print\n0107 message\n"
+    );
+}
+
+#[test]
+fn base() {
+    for (base, expected) in [
+        ("hex", "0001\nbeef\nffff\n"),
+        ("dec", "1\n48879\n65535\n"),
+        (
+            "bin",
+            "0000000000000001\n1011111011101111\n1111111111111111\n",
+        ),
+    ] {
+        let mut ass = Command::new(BIN)
+            .args(["--base", base])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        ass.stdin
+            .take()
+            .unwrap()
+            .write_all(b"dw 1, 0xbeef, -1")
+            .unwrap();
+        let output = ass.wait_with_output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(str::from_utf8(&output.stdout).unwrap(), expected);
+    }
+}
+
+#[test]
+fn strict_branches() {
+    let assemble = |args: &[&str], input: &[u8]| {
+        let mut ass = Command::new(BIN)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        ass.stdin.take().unwrap().write_all(input).unwrap();
+        let output = ass.wait_with_output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let far = b"jump far\nresw 40000\nfar: halt\n";
+
+    // wrapping is fine by default
+    assert_eq!(assemble(&[], far), (true, String::new()));
+    assert_eq!(assemble(&[], b"jump -4\n"), (true, String::new()));
+
+    let (ok, err) = assemble(&["--strict-branches"], far);
+    assert!(!ok);
+    assert!(
+        err.ends_with("jump far, target: far, distance: 40002\n"),
+        "{err}"
+    );
+    let (ok, err) = assemble(&["--strict-branches"], b"jump -4\n");
+    assert!(!ok);
+    assert!(
+        err.ends_with("jump -4, target: -4, distance: -4\n"),
+        "{err}"
+    );
+
+    let near = b"jump end\nresw 20\nend: halt\n";
+    assert!(assemble(&["--strict-branches"], near).0);
+    let (ok, err) = assemble(&["--strict-branches", "--branch-limit", "10"], near);
+    assert!(!ok);
+    assert!(
+        err.ends_with("jump end, target: end, distance: 22\n"),
+        "{err}"
+    );
+
+    // code that runs off the end of memory jumps by wrapping
+    let uninit = std::fs::read(Path::new(DATA_DIR).join("uninit.asm")).unwrap();
+    assert!(assemble(&["--h16", "fffe"], &uninit).0);
+    assert!(!assemble(&["--strict-branches", "--h16", "fffe"], &uninit).0);
+}
+
+#[test]
+fn align_after_skip() {
+    let assemble = |input: &[u8]| {
+        let mut ass = Command::new(BIN)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        ass.stdin.take().unwrap().write_all(input).unwrap();
+        ass.wait_with_output().unwrap().status.success()
+    };
+    // the skip would land on the padding instead of the jump
+    assert!(!assemble(b"skne A, B\nalign 4\njump 0\n"));
+    assert!(!assemble(b"skne A, B\nskne A, C\nalign 4\njump 0\n"));
+    // already aligned, so there is no padding to land on
+    assert!(assemble(b"dw 0, 0, 0\nskne A, B\nalign 4\njump 0\n"));
+    // the skipped jump has started, so landing on a nop after it is fine
+    assert!(assemble(b"skne A, B\njump 0\nalign 4\nhalt\n"));
+}